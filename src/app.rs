@@ -1,5 +1,15 @@
 use crate::data::Directory;
-use crate::utils::canonicalize_dir_str;
+use crate::database::{parse_dirs, CorruptRecord};
+use crate::score::ScoreModel;
+use crate::exclude::Exclusions;
+use crate::OPTIONS;
+use crate::picker::{pick, pick_external, Picked, PickerItem};
+use crate::selection::parse_selection;
+use crate::matcher::{compile_patterns, match_quality, Folding, QUERY_MATCHERS};
+use crate::import::{parse_imported, parse_json_dirs, IMPORT_FORMATS};
+use crate::export::{export_dirs, EXPORT_FORMATS};
+use crate::json::escape_string;
+use crate::utils::{canonicalize_dir_str, git_root};
 use crate::utils::write_dir;
 
 use std::fs::metadata;
@@ -12,9 +22,13 @@ use crate::colors::{color_code, sgr_code};
 use std::path::Path;
 use std::path::PathBuf;
 use std::fs;
+use std::collections::HashMap;
 
 
 
+pub(crate) const CURRENT_DIR_POLICIES: [&str; 3] = ["skip", "demote", "keep"];
+
+
 pub(crate) fn get_current_seconds() -> i64 {
     return SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
 }
@@ -29,10 +43,39 @@ pub(crate) fn get_user() -> String {
     return current_user;
 }
 
+/// Replace '/home/<user>' with '~' and '(/run)/media/<user>' with '>'.
+pub(crate) fn compact_path(dir_name: &str) -> String {
+    let current_home_dir = get_home_dir();
+    let dir_name = dir_name.replace(current_home_dir.as_str(), "~");
+    let user = get_user();
+    let media_user = format!("/media/{}", user);
+    let run_media_user = format!("/run/media/{}", user);
+    dir_name.replace(run_media_user.as_str(), ">")
+        .replace(media_user.as_str(), ">")
+}
+
 #[derive(Debug, Clone)]
 pub struct SelectionError;
 
 
+/// Error of a search: an invalid regex or glob, or a scope that does not
+/// apply. It is shown with `show_error`, except by 'cz query'.
+pub(crate) struct SearchError {
+    message: String,
+    details: String,
+}
+
+
+/// Where 'z' goes, as found by `find_dir`.
+pub(crate) enum Target {
+    /// An alias, an existing path, or the best matching dir
+    Dir(String),
+    /// The matching dirs (sorted by score), to select one interactively
+    Choice(Vec<Directory>),
+    NoMatch,
+}
+
+
 #[allow(dead_code)]
 pub(crate) struct App <'a> {
     pub(crate) theme: String,
@@ -43,7 +86,26 @@ pub(crate) struct App <'a> {
     pub(crate) substring: String,
     pub(crate) show_files: String,
     pub(crate) nav_start_number: usize,
+    pub(crate) max_age: i64,
+    pub(crate) max_entries: usize,
+    pub(crate) max_total_count: i64,
+    pub(crate) keep_aliased: bool,
+    pub(crate) score_model: ScoreModel,
+    pub(crate) matcher: String,
+    pub(crate) case_sensitivity: String,
+    pub(crate) fold_unicode: bool,
+    pub(crate) exclusions: Exclusions,
+    pub(crate) fallback: bool,
+    pub(crate) fallback_dirs: usize,
+    pub(crate) proximity_boost: f64,
+    pub(crate) proximity_git_root: bool,
+    /// Set with '--repo' and '--in-repo'
+    pub(crate) scope: String,
+    pub(crate) current_dir: String,
+    pub(crate) picker: String,
     pub(crate) dirs: &'a mut Vec<Directory>,
+    pub(crate) loaded_dirs: Vec<Directory>,
+    pub(crate) corrupt_records: Vec<CorruptRecord>,
 }
 
 impl App <'_> {
@@ -91,6 +153,29 @@ impl App <'_> {
     }
 
 
+    /// Return the dirs found by a search, or show its error and exit.
+    fn valid_or_exit(&self, valid_dirs: Result<Vec<Directory>, SearchError>) -> Vec<Directory> {
+        match valid_dirs {
+            Ok(valid_dirs) => valid_dirs,
+            Err(error) => {
+                self.show_error(&error.message, &error.details);
+                Vec::new()
+            }
+        }
+    }
+
+
+    fn target_or_exit(&self, target: Result<Target, SearchError>) -> Target {
+        match target {
+            Ok(target) => target,
+            Err(error) => {
+                self.show_error(&error.message, &error.details);
+                Target::NoMatch
+            }
+        }
+    }
+
+
     pub(crate) fn show_exit_message(&self, text: &str) {
         self.printf("bold", "green", String::from(text));
     }
@@ -124,20 +209,11 @@ impl App <'_> {
             self.show_exit_message("No dirs");
         } else {
             // Show valid dirs
-            for (i, dir) in valid_dirs.iter().enumerate() {
+            for (i, dir) in valid_dirs.iter().enumerate().take(max_results) {
                 let mut dir_name = dir.name.clone();
 
                 if self.compact_paths {
-                    // Replace /home/<user> with '~'
-                    let current_home_dir = get_home_dir();
-                    dir_name = dir_name.replace(current_home_dir.as_str(), "~");
-
-                    // Replace (/run)/media/<user> with '>'
-                    let user = get_user();
-                    let media_user = format!("/media/{}", user);
-                    let run_media_user = format!("/run/media/{}", user);
-                    dir_name = dir_name.replace(run_media_user.as_str(), ">");
-                    dir_name = dir_name.replace(media_user.as_str(), ">");
+                    dir_name = compact_path(&dir_name);
                 }
 
                 let mut alias = String::new();
@@ -153,9 +229,39 @@ impl App <'_> {
                     (i+start),
                     // dir.score
                 );
-                if i == (max_results - start) {
-                    break;
-                }
+            }
+        }
+    }
+
+
+    /// Show the dirs a page at a time, numbered across pages, and read the
+    /// answer of the user; the paging commands are handled here: 'n' (or
+    /// 'e') shows the next page, 'p' the previous one, and 'f' and 'l' the
+    /// first and the last ones.
+    fn read_selection(&self, valid_dirs: &[Directory], max_num: usize) -> String {
+        let page_size = if max_num == 0 { self.max_results } else { max_num }.max(1);
+        let number_of_pages = valid_dirs.len().div_ceil(page_size);
+        let last_page = number_of_pages.saturating_sub(1);
+        let mut page = 0;
+        loop {
+            let start = page * page_size;
+            let end = (start + page_size).min(valid_dirs.len());
+            if number_of_pages > 1 {
+                println!(
+                    "[{}/{}] {}-{} of {} (n: next, p: previous, f: first, l: last)",
+                    page + 1, number_of_pages, start + 1, end, valid_dirs.len(),
+                );
+            }
+            self.list_dirs(&valid_dirs[start..end].to_vec(), page_size, start + 1);
+            println!();
+
+            let answer = self.select_dir();
+            match answer.trim() {
+                "n" | "e" => { page = (page + 1).min(last_page); }
+                "p" => { page = page.saturating_sub(1); }
+                "f" => { page = 0; }
+                "l" => { page = last_page; }
+                _ => { return answer; }
             }
         }
     }
@@ -171,14 +277,87 @@ impl App <'_> {
     }
 
 
+    /// Entries of the picker for the dirs, listed as by `list_dirs`, with
+    /// their full paths as preview.
+    fn picker_items(&self, valid_dirs: &[Directory]) -> Vec<PickerItem> {
+        valid_dirs.iter()
+            .map(|dir| {
+                let mut label = dir.name.clone();
+                if self.compact_paths {
+                    label = compact_path(&label);
+                }
+                if !dir.alias.is_empty() {
+                    label = format!("{}:{}", dir.alias, label);
+                }
+                PickerItem {
+                    label,
+                    preview: dir.name.clone(),
+                    selectable: true,
+                }
+            })
+            .collect()
+    }
+
+
+    /// Pick dirs with the configured picker; the numbered list is used when
+    /// it is not available.
+    fn pick_dirs(&self, valid_dirs: &[Directory], multi: bool) -> Picked {
+        if valid_dirs.is_empty() {
+            return Picked::Unavailable;
+        }
+        match self.picker.as_str() {
+            "builtin" => {
+                let header = if multi { "Tab: mark" } else { "" };
+                pick(self, header, &self.picker_items(valid_dirs), multi)
+            }
+            "numbered" => Picked::Unavailable,
+            program => {
+                // Score and alias columns, and the full path
+                let lines: Vec<String> = valid_dirs.iter()
+                    .map(|dir| format!("{:>10.1}\t{}\t{}", dir.score, dir.alias, dir.name))
+                    .collect();
+                pick_external(program, &lines, multi)
+            }
+        }
+    }
+
+
     pub(crate) fn select_valid_dir_no_exit(
         &self,
         valid_dirs: Vec<Directory>,
         max_num: usize,
         start: usize,
         files: Vec<String>,
+        base_dir: &str,
     ) -> Result<String, SelectionError>
     {
+        if self.picker != "numbered" {
+            // Files are only shown
+            let file_items = files.iter().map(|file| PickerItem {
+                label: file.clone(),
+                preview: String::new(),
+                selectable: false,
+            });
+            let mut items: Vec<PickerItem> = Vec::new();
+            if self.show_files == "top" {
+                items.extend(file_items);
+                items.extend(self.picker_items(&valid_dirs));
+            } else if self.show_files == "bottom" {
+                items.extend(self.picker_items(&valid_dirs));
+                items.extend(file_items);
+            } else {
+                items.extend(self.picker_items(&valid_dirs));
+            }
+            let offset = if self.show_files == "top" { files.len() } else { 0 };
+            for item in items.iter_mut().filter(|item| item.selectable) {
+                item.preview = Path::new(base_dir).join(&item.preview).to_string_lossy().to_string();
+            }
+            match pick(self, base_dir, &items, false) {
+                Picked::Selected(indexes) => { return Ok(valid_dirs[indexes[0] - offset].name.clone()); }
+                Picked::Cancelled => { return Err(SelectionError); }
+                Picked::Unavailable => {}
+            }
+        }
         if self.show_files == "top" {
             App::print_files(files.clone());
         }
@@ -209,27 +388,27 @@ impl App <'_> {
 
 
     pub(crate) fn select_valid_dirs(&self, valid_dirs: Vec<Directory>, max_num: usize) -> Result<Vec<String>, String> {
-
-        self.list_dirs(&valid_dirs, max_num, 1);
-        println!();
-
-        // Select dirs by numbers
-        let selected_dirs_string = self.select_dir();
-        // parse list of numbers separated by spaces
-        let selected_dirs_nums_str: Vec<&str> = selected_dirs_string.split(' ').collect();
-        // selected dirs names strs
-        let mut selected_dirs_strings: Vec<String> = Vec::new();
-
-        for selected_dir_num_str in selected_dirs_nums_str {
-
-            let selected_dir_num = self.parse_and_validate_dir_number(&selected_dir_num_str, valid_dirs.len()).unwrap();
-
-            // Get name of the selected dir and add it to the list
-            selected_dirs_strings.push(valid_dirs[selected_dir_num-1].name.clone());
-            // let dir_name = format!("{}", valid_dirs[selected_dir_num-1].name);
+        match self.pick_dirs(&valid_dirs, true) {
+            Picked::Selected(indexes) => {
+                return Ok(indexes.iter().map(|i| valid_dirs[*i].name.clone()).collect());
+            }
+            Picked::Cancelled => {
+                self.show_error("No dir selected", "");
+                return Err("Cancelled".to_string());
+            }
+            Picked::Unavailable => {}
         }
 
-        return Ok(selected_dirs_strings);
+        // Select dirs by numbers and ranges
+        let selection = self.read_selection(&valid_dirs, max_num);
+        match parse_selection(&selection, valid_dirs.len()) {
+            Ok(numbers) => Ok(numbers.iter().map(|i| valid_dirs[i-1].name.clone()).collect()),
+            Err(errors) => {
+                // Nothing is selected if any token is wrong
+                self.show_error("Invalid selection", &errors.join(", "));
+                Err(errors.join(", "))
+            }
+        }
     }
 
 
@@ -266,34 +445,20 @@ impl App <'_> {
 
 
     pub(crate) fn select_valid_dir(&self, valid_dirs: Vec<Directory>, max_num: usize) -> Result<String, String> {
-        let mut i = 0;
-        let mut selected_dir: String;
-        let mut dirs_to_show = &valid_dirs[0..];
-        let mut starting_index = 0;
-        let number_of_pages = (valid_dirs.len() + self.max_results - 1) / self.max_results;
-
-        loop {
-            if number_of_pages > 0 {
-                println!("[{}/{}]", i+1, number_of_pages);
-            }
-            self.list_dirs(&dirs_to_show.to_vec(), max_num, 1);
-            println!();
-
-            selected_dir = self.select_dir();
-            if selected_dir != "e" { break; }
-            i = i + 1;
-            starting_index = i * self.max_results;
-            if starting_index >= valid_dirs.len() {
-                starting_index = 0;
-                i = 0;
+        match self.pick_dirs(&valid_dirs, false) {
+            Picked::Selected(indexes) => { return Ok(valid_dirs[indexes[0]].name.clone()); }
+            Picked::Cancelled => {
+                self.show_error("No dir selected", "");
+                return Err("Cancelled".to_string());
             }
-            dirs_to_show = &valid_dirs[starting_index..];
+            Picked::Unavailable => {}
         }
-
-        let selected_dir_num = self.parse_and_validate_dir_number(&selected_dir, dirs_to_show.len()).unwrap();
+        // Numbers are the same in all the pages
+        let selected_dir = self.read_selection(&valid_dirs, max_num);
+        let selected_dir_num = self.parse_and_validate_dir_number(&selected_dir, valid_dirs.len()).unwrap();
 
         // Get name of the selected dir
-        let dir_name = format!("{}", valid_dirs[starting_index+selected_dir_num-1].name);
+        let dir_name = valid_dirs[selected_dir_num-1].name.to_string();
 
         return Ok(dir_name);
     }
@@ -315,107 +480,244 @@ impl App <'_> {
         if forced_substring != "none" {
             starting_index = 2;
         }
-        let mut dir_str = args[starting_index].as_str();
+        let patterns = &args[starting_index..];
 
-        // If string is an alias, then cd to the directory, if exists
-        match self.get_by_alias(dir_str) {
-            Ok(dir) => {
-                let dir_str = dir.name.as_str();
-                if Path::new(dir_str).exists()
-                    && metadata(dir_str).unwrap().is_dir()
-                {
-                    self.direct_cd(dir.name);
+        let target = self.find_dir(patterns, forced_substring);
+        match self.target_or_exit(target) {
+            Target::Dir(dir_name) => { self.direct_cd(dir_name); }
+            Target::Choice(valid_dirs) => {
+                // Interactively select dir among all the dirs that
+                // match the substring(s)
+                let dir_name = self.select_valid_dir(valid_dirs, 0).unwrap();
+                self.direct_cd(dir_name);
+            }
+            Target::NoMatch => { self.fallback_cd(patterns); }
+        }
+    }
+
+
+    /// Find the dir to go to: the dir of an alias, an existing path, or
+    /// the top dir that matches the substrings, which depends on the
+    /// 'substring' option (unless it is forced by '-t', '-e' or '-b').
+    pub(crate) fn find_dir(&mut self, patterns: &[String], forced_substring: &str) -> Result<Target, SearchError> {
+        let substring = if forced_substring == "none" {
+            self.substring.clone()
+        } else {
+            forced_substring.to_string()
+        };
+
+        // Regexes and globs are never aliases nor paths, and aliases and
+        //   paths are not restricted to a scope
+        let search_only = patterns.is_empty()
+            || QUERY_MATCHERS.contains(&self.matcher.as_str())
+            || self.scope != "all";
+        if !search_only {
+            let dir_str = patterns[0].as_str();
+            // If string is an alias, then go to the directory, if exists
+            if let Ok(dir) = self.get_by_alias(dir_str) {
+                if Path::new(&dir.name).is_dir() {
+                    return Ok(Target::Dir(dir.name));
                 }
-            },
-            Err(_) => {
-                // If it is a dir AND exists in the FS
-                if Path::new(dir_str).exists()
-                    && metadata(dir_str).unwrap().is_dir()
-                {
-                    let canonical_dir = canonicalize_dir_str(dir_str);
-                    dir_str = canonical_dir.as_str();
-
-                    // Check if dir is in the table
-                    match self.get(dir_str) {
-                        Ok(dir) => {
-                            // If the dir is not in the table and it does exists in the
-                            //   FS, add it
-                            self.direct_cd(dir.name);
-                        },
-                        Err(_) => {
-                            self.direct_cd(dir_str.to_string());
-                        }
+                return Ok(Target::NoMatch);
+            }
+            // If it is a dir AND exists in the FS
+            if Path::new(dir_str).is_dir() {
+                return Ok(Target::Dir(canonicalize_dir_str(dir_str)));
+            }
+        }
+
+        if substring == "basename" {
+            // The best dir whose basename matches the substring(s)
+            let valid_dirs = self.get_valid_with("basename", patterns.to_vec(), false)?;
+            return match valid_dirs.into_iter().next() {
+                Some(dir) => Ok(Target::Dir(dir.name)),
+                None => Ok(Target::NoMatch),
+            };
+        }
+
+        let valid_dirs = self.get_valid(patterns.to_vec(), false)?;
+        if valid_dirs.is_empty() {
+            return Ok(Target::NoMatch);
+        }
+        // If there is only one result, go to it
+        if valid_dirs.len() == 1 || substring == "score" {
+            // The dir with the highest score
+            return Ok(Target::Dir(valid_dirs[0].name.clone()));
+        }
+        if substring == "shortest" {
+            // The current dir is only chosen if it is the only match (with
+            //   'skip', it is not even a match)
+            let current_dir = env::current_dir().unwrap_or_default();
+            let selected_dir = valid_dirs.iter()
+                .filter(|dir| self.current_dir == "keep" || Path::new(&dir.name) != current_dir)
+                .min_by_key(|dir| dir.name.len())
+                .unwrap_or(&valid_dirs[0]);
+            return Ok(Target::Dir(selected_dir.name.clone()));
+        }
+        Ok(Target::Choice(valid_dirs))
+    }
+
+
+    /// Print the dir that 'z' would go to, or all the matching dirs (with
+    /// '--list'), as plain text or as JSON, one per line, without asking
+    /// anything; exit with status 1 if no dir matches.
+    pub(crate) fn query(&mut self, args: &[String]) {
+        let list = args.iter().any(|arg| arg == "--list");
+        let show_score = args.iter().any(|arg| arg == "--score");
+        let json = args.iter().any(|arg| arg == "--json");
+        let patterns: Vec<String> = args.iter()
+            .filter(|arg| !["--list", "--score", "--json"].contains(&arg.as_str()))
+            .cloned()
+            .collect();
+
+        let dirs = if list {
+            self.get_valid(patterns, false)
+        } else {
+            self.find_dir(&patterns, "none").map(|target| {
+                let dir_name = match target {
+                    Target::Dir(dir_name) => Some(dir_name),
+                    // Without asking, the dir with the highest score
+                    Target::Choice(valid_dirs) => Some(valid_dirs[0].name.clone()),
+                    Target::NoMatch => self.fallback_dir(&patterns),
+                };
+                match dir_name {
+                    Some(dir_name) => {
+                        // Dirs that are not recorded have no score
+                        let dir = self.get(&dir_name).unwrap_or(Directory {
+                            name: dir_name,
+                            counter: 0,
+                            last_access: 0,
+                            score: 0.0,
+                            alias: String::new(),
+                        });
+                        vec![dir]
                     }
-                } else { // if arguments are substrings, go to the top results that matches
-                         // the substrings
-                    // Get shortest directory
-                    let valid_dirs = self.get_valid(
-                        Vec::from(&args[starting_index..]), false
-                    ).unwrap();
-
-                    if valid_dirs.is_empty() {
-                        self.show_exit_message("No dirs");
-                    } else {
-                        // If there is only one result, cd to it
-                        if valid_dirs.len() == 1
-                            || (self.substring == "score" && forced_substring == "none")
-                            || forced_substring == "score"
-                        {
-                            // Access the substring with the highest score
-                            let selected_dir = valid_dirs[0].name.clone();
-                            // app.direct_cd(&conn, selected_dir.clone());
-                            self.direct_cd(selected_dir);
-                        } else {
-                            // Access the top dir that matches the substring(s)
-                            // Top depends on criteria
-                            if (self.substring == "shortest" && forced_substring == "none")
-                                || forced_substring == "shortest"
-                            {
-                                let mut selected_dir = valid_dirs[0].name.as_str();
-                                for dir in valid_dirs.iter() {
-                                    if dir.name.len() < selected_dir.len() {
-                                        selected_dir = dir.name.as_str();
-                                    }
-                                }
-                                // app.direct_cd(&conn, selected_dir.to_string());
-                                self.direct_cd(selected_dir.to_string());
-                            }
-                            else if (self.substring == "basename" && forced_substring == "none")
-                                || forced_substring == "basename"
-                            {
-                                let mut cded = false;
-                                for dir in valid_dirs.iter() {
-                                    // Access the basename of the dir that matches the substring(s)
-                                    let basename = PathBuf::from(dir.name.clone());
-                                    let basename_str = basename.file_name().unwrap().to_str().unwrap().to_string();
-                                    let mut all_match = true;
-                                    for pattern in args[starting_index..].iter() {
-                                        if !basename_str.contains(pattern) {
-                                            all_match = false;
-                                            break;
-                                        }
-                                    }
-                                    if all_match {
-                                        self.direct_cd(dir.name.clone());
-                                        cded = true;
-                                        break;
-                                    }
-                                }
-                                if !cded {
-                                    self.show_exit_message("No dirs");
-                                }
-                            } else {
-                                // Interactively select dir among all the dirs that
-                                // match the substring(s)
-                                let dir_name = self.select_valid_dir(valid_dirs, 0).unwrap();
-                                // app.direct_cd(&conn, dir_name.clone());
-                                self.direct_cd(dir_name);
-                            }
-                        }
+                    None => Vec::new(),
+                }
+            })
+        };
+
+        // Errors go to stderr, without colors nor touching the output file
+        let dirs = match dirs {
+            Ok(dirs) => dirs,
+            Err(error) => {
+                eprintln!("{}: {}", error.message, error.details);
+                exit(1);
+            }
+        };
+        if dirs.is_empty() {
+            exit(1);
+        }
+        for dir in dirs.iter() {
+            if json {
+                let score = if show_score { format!(", \"score\": {}", dir.score) } else { String::new() };
+                println!(
+                    "{{\"name\": {}{}, \"alias\": {}}}",
+                    escape_string(&dir.name),
+                    score,
+                    escape_string(&dir.alias),
+                );
+            } else if show_score {
+                println!("{:.1}\t{}", dir.score, dir.name);
+            } else {
+                println!("{}", dir.name);
+            }
+        }
+    }
+
+
+    /// When no recorded dir matches, look for the query in the filesystem:
+    /// as a path relative to the current dir and to the CDPATH entries,
+    /// and then among the subdirectories of the top dirs (the one whose name
+    /// matches best, weighted by the score of its parent).
+    fn fallback_dir(&mut self, patterns: &[String]) -> Option<String> {
+        // Without substrings, the relative path would be the current dir
+        if patterns.is_empty()
+            || !self.fallback
+            || QUERY_MATCHERS.contains(&self.matcher.as_str())
+            || self.scope != "all"
+        {
+            return None;
+        }
+        let relative_path = patterns.join("/");
+        let mut bases = vec![".".to_string()];
+        if let Ok(cdpath) = env::var("CDPATH") {
+            bases.extend(cdpath.split(':').filter(|base| !base.is_empty()).map(String::from));
+        }
+        for base in bases.iter() {
+            let candidate = Path::new(base).join(&relative_path);
+            if candidate.is_dir() {
+                return Some(canonicalize_dir_str(candidate.to_str()?));
+            }
+        }
+
+        let top_dirs = self.get_valid(Vec::new(), false).unwrap_or_default();
+        let folding = Folding::new(&self.case_sensitivity, self.fold_unicode, patterns);
+        let patterns: Vec<String> = patterns.iter().map(|pattern| folding.fold(pattern)).collect();
+        let mut best_dir: Option<(f64, String)> = None;
+        for dir in top_dirs.iter().take(self.fallback_dirs) {
+            let entries = match fs::read_dir(&dir.name) {
+                Ok(entries) => entries,
+                Err(_) => { continue; }
+            };
+            for entry in entries.flatten() {
+                let path = entry.path().to_string_lossy().to_string();
+                if !entry.path().is_dir() || self.exclusions.is_excluded(&path) {
+                    continue;
+                }
+                let name = entry.file_name().to_string_lossy().to_string();
+                if let Some(quality) = match_quality(&self.matcher, &folding.fold(&name), &patterns) {
+                    let score = dir.score * quality;
+                    if best_dir.as_ref().is_none_or(|(best_score, _)| score > *best_score) {
+                        best_dir = Some((score, path));
                     }
                 }
-            },
+            }
+        }
+        best_dir.map(|(_, path)| canonicalize_dir_str(&path))
+    }
+
+
+    /// Jump to the dir found by `fallback_dir`, recording it.
+    fn fallback_cd(&mut self, patterns: &[String]) {
+        match self.fallback_dir(patterns) {
+            Some(dir_name) => { self.direct_cd(dir_name); }
+            None => { self.show_exit_message("No dirs"); }
+        }
+    }
+
+
+    /// Print the completion candidates of the words typed after 'z', the
+    /// last one being the word under completion: the options, if it starts
+    /// with '-'; otherwise, the aliases that start with it and the dirs that
+    /// match the typed substrings.
+    pub(crate) fn complete(&mut self, words: &[String]) {
+        let current_word = match words.last() {
+            Some(word) => word.as_str(),
+            None => "",
         };
+        if current_word.starts_with('-') {
+            for option in OPTIONS.iter().filter(|option| option.starts_with(current_word)) {
+                println!("{}", option);
+            }
+            return;
+        }
+        let patterns: Vec<String> = words.iter()
+            .filter(|word| !word.is_empty() && !word.starts_with('-'))
+            .cloned()
+            .collect();
+        if patterns.len() <= 1 {
+            for dir in self.dirs.iter() {
+                if !dir.alias.is_empty() && dir.alias.starts_with(current_word) {
+                    println!("{}", dir.alias);
+                }
+            }
+        }
+        // Invalid patterns have no candidates
+        let valid_dirs = self.get_valid(patterns, false).unwrap_or_default();
+        for dir in valid_dirs.iter().take(self.max_results) {
+            println!("{}", dir.name);
+        }
     }
 
 
@@ -423,7 +725,8 @@ impl App <'_> {
         if args.len() < 3 {
             self.show_error("No substring provided", "");
         } else {
-            let valid_dirs = self.get_valid(Vec::from(&args[2..]), false).unwrap();
+            let valid_dirs = self.get_valid(Vec::from(&args[2..]), false);
+            let valid_dirs = self.valid_or_exit(valid_dirs);
             if valid_dirs.is_empty() {
                 self.show_exit_message("No dirs");
             } else {
@@ -453,7 +756,8 @@ impl App <'_> {
             println!("Aliased dirs");
             let valid_dirs = self.get_valid(
                 Vec::new(), true
-            ).unwrap();
+            );
+            let valid_dirs = self.valid_or_exit(valid_dirs);
 
             // Always list dirs
             let dir_name = self.select_valid_dir(valid_dirs, 0).unwrap();
@@ -504,7 +808,8 @@ impl App <'_> {
                 // app.show_error("The provided directory does not exist", "");
                 let valid_dirs = self.get_valid(
                     Vec::new(), false
-                ).unwrap();
+                );
+                let valid_dirs = self.valid_or_exit(valid_dirs);
 
                 // Always list dirs
                 let dir_name = self.select_valid_dir(valid_dirs, 0).unwrap();
@@ -517,19 +822,53 @@ impl App <'_> {
     }
 
 
+    pub(crate) fn add_dirs(&mut self, dir_strs: &[String]) {
+        if dir_strs.is_empty() {
+            eprintln!("No directory provided");
+            exit(1);
+        }
+        for dir_str in dir_strs {
+            if Path::new(dir_str).is_dir() {
+                let canonical_dir = canonicalize_dir_str(dir_str);
+                self.insert(canonical_dir.as_str());
+            } else {
+                // Do not print to stdout, since it is run from shell hooks
+                eprintln!("Directory does not exist: {}", dir_str);
+            }
+        }
+    }
+
+
     pub(crate) fn insert(&mut self, dir: &str) {
         self.insert_with_alias(dir, None);
     }
 
 
+    /// Compute the score of every dir with the configured model.
+    pub(crate) fn update_scores(&mut self) {
+        let current_seconds = get_current_seconds();
+        for dir in self.dirs.iter_mut() {
+            dir.score = self.score_model.score(dir.counter, dir.last_access, current_seconds);
+        }
+    }
+
+
     pub(crate) fn insert_with_alias(&mut self, dir: &str, alias: Option<&str>) {
         // println!("inserting dir: {}", dir);
+        // Excluded dirs are not recorded, unless they have an alias
+        let aliased = alias.is_some()
+            || self.dirs.iter().any(|d| d.name == dir && !d.alias.is_empty());
+        if !aliased && self.exclusions.is_excluded(dir) {
+            return;
+        }
         // Check if dir is already in dirs
         let mut found = false;
+        let current_seconds = get_current_seconds();
         for d in self.dirs.iter_mut() {
             if d.name == dir {
                 d.counter += 1;
-                d.last_access = get_current_seconds();
+                d.last_access = current_seconds;
+                d.score = self.score_model.score(d.counter, d.last_access, current_seconds);
                 if let Some(alias) = alias {
                     d.alias = alias.to_string();
                 }
@@ -546,8 +885,8 @@ impl App <'_> {
             let dir = Directory {
                 name: dir.to_string(),
                 counter: 1,
-                last_access: get_current_seconds(),
-                score: 0.0,
+                last_access: current_seconds,
+                score: self.score_model.score(1, current_seconds, current_seconds),
                 alias: alias.to_string(),
             };
             self.dirs.push(dir);
@@ -561,6 +900,14 @@ impl App <'_> {
                 return Ok(dir.clone());
             }
         }
+        // Otherwise, compare them as the substrings are compared
+        let folding = Folding::new(&self.case_sensitivity, self.fold_unicode, &[alias.to_string()]);
+        let alias = folding.fold(alias);
+        for dir in self.dirs.iter() {
+            if !dir.alias.is_empty() && folding.fold(&dir.alias) == alias {
+                return Ok(dir.clone());
+            }
+        }
         Err("Alias not found".to_string())
     }
 
@@ -579,31 +926,74 @@ impl App <'_> {
         &mut self,
         patterns: Vec<String>,
         alias_only: bool,
-    ) -> Result<Vec<Directory>, String> {
+    ) -> Result<Vec<Directory>, SearchError> {
+        let matcher = self.matcher.clone();
+        self.get_valid_with(&matcher, patterns, alias_only)
+    }
+
+
+    /// Like `get_valid`, but with a given matcher instead of the configured
+    /// one.
+    pub(crate) fn get_valid_with(
+        &mut self,
+        matcher: &str,
+        patterns: Vec<String>,
+        alias_only: bool,
+    ) -> Result<Vec<Directory>, SearchError> {
         // Filter invalid dirs from the current path
         let mut valid_dirs: Vec<Directory> = Vec::new();
 
         // Sort by score
         self.dirs.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
 
+        // Regexes and globs are matched against both the stored and the
+        //   displayed names, as they are
+        if QUERY_MATCHERS.contains(&matcher) {
+            let regexes = match compile_patterns(matcher, &patterns) {
+                Ok(regexes) => regexes,
+                Err(error) => {
+                    return Err(SearchError {
+                        message: format!("Invalid {}", matcher),
+                        details: error,
+                    });
+                }
+            };
+            let mut valid_dirs: Vec<Directory> = Vec::new();
+            for dir in self.dirs.iter() {
+                let names = [dir.name.clone(), compact_path(&dir.name)];
+                let all_match = regexes.iter()
+                    .all(|regex| names.iter().any(|name| regex.is_match(name)));
+                if all_match
+                    && (!alias_only || !dir.alias.is_empty())
+                    && Path::new(&dir.name).exists()
+                {
+                    valid_dirs.push(dir.clone());
+                }
+            }
+            let mut valid_dirs = self.filter_scope(valid_dirs)?;
+            self.apply_proximity(&mut valid_dirs);
+            valid_dirs.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+            return Ok(self.order_current_dir(valid_dirs));
+        }
+
         // Filter by pattern
+        let folding = Folding::new(&self.case_sensitivity, self.fold_unicode, &patterns);
+        let patterns: Vec<String> = patterns.iter().map(|pattern| folding.fold(pattern)).collect();
         let mut filtered_dirs: Vec<Directory> = Vec::new();
         for dir in self.dirs.iter() {
             if patterns.is_empty() {
                 filtered_dirs.push(dir.clone());
-            } else {
-                let mut all_match = true;
-                for pattern in patterns.iter() {
-                    if !dir.name.contains(pattern) {
-                        all_match = false;
-                        break;
-                    }
-                }
-                if all_match {
-                    filtered_dirs.push(dir.clone());
-                }
+            } else if let Some(quality) = match_quality(matcher, &folding.fold(&dir.name), &patterns) {
+                // Weight the score by how well the dir matches
+                let mut dir = dir.clone();
+                dir.score *= quality;
+                filtered_dirs.push(dir);
             }
         }
+        let mut filtered_dirs = self.filter_scope(filtered_dirs)?;
+        self.apply_proximity(&mut filtered_dirs);
+        filtered_dirs.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        let mut filtered_dirs = self.order_current_dir(filtered_dirs);
 
         // Filter by alias
         if alias_only {
@@ -627,6 +1017,85 @@ impl App <'_> {
     }
 
 
+    /// Jumping to the current dir is pointless, so, depending on
+    /// 'current_dir', it is removed from the sorted dirs ('skip') or moved to
+    /// the end ('demote').
+    fn order_current_dir(&self, dirs: Vec<Directory>) -> Vec<Directory> {
+        if self.current_dir == "keep" {
+            return dirs;
+        }
+        let current_dir = match env::current_dir() {
+            Ok(current_dir) => current_dir,
+            Err(_) => { return dirs; }
+        };
+        let (current, mut others): (Vec<Directory>, Vec<Directory>) = dirs.into_iter()
+            .partition(|dir| Path::new(&dir.name) == current_dir);
+        if self.current_dir == "demote" {
+            others.extend(current);
+        }
+        others
+    }
+
+
+    /// Keep the dirs in the scope set with '--repo' (roots of git
+    /// repositories) or '--in-repo' (dirs in the git repository of the
+    /// current dir).
+    fn filter_scope(&self, dirs: Vec<Directory>) -> Result<Vec<Directory>, SearchError> {
+        match self.scope.as_str() {
+            "repo" => {
+                Ok(dirs.into_iter()
+                    .filter(|dir| Path::new(&dir.name).join(".git").exists())
+                    .collect())
+            }
+            "in-repo" => {
+                let current_dir = env::current_dir().unwrap_or_default();
+                let repository_root = match git_root(&current_dir) {
+                    Some(root) => root,
+                    None => {
+                        return Err(SearchError {
+                            message: "Not in a git repository".to_string(),
+                            details: current_dir.to_string_lossy().to_string(),
+                        });
+                    }
+                };
+                Ok(dirs.into_iter()
+                    .filter(|dir| Path::new(&dir.name).starts_with(&repository_root))
+                    .collect())
+            }
+            _ => Ok(dirs),
+        }
+    }
+
+
+    /// Boost the scores of the dirs near the current one. The proximity is
+    /// 1 for the dirs inside the current one (or in its git repository, with
+    /// 'proximity_git_root'), and it halves with each level that has to be
+    /// climbed from the current dir to reach them.
+    fn apply_proximity(&self, dirs: &mut [Directory]) {
+        if self.proximity_boost <= 0.0 {
+            return;
+        }
+        let current_dir = match env::current_dir() {
+            Ok(current_dir) => current_dir,
+            Err(_) => { return; }
+        };
+        let repository_root = if self.proximity_git_root { git_root(&current_dir) } else { None };
+        let current_depth = current_dir.components().count();
+        for dir in dirs.iter_mut() {
+            let path = Path::new(&dir.name);
+            let shared = path.components()
+                .zip(current_dir.components())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let mut proximity = 0.5_f64.powi((current_depth - shared) as i32);
+            if repository_root.as_ref().is_some_and(|root| path.starts_with(root)) {
+                proximity = 1.0;
+            }
+            dir.score *= 1.0 + self.proximity_boost * proximity;
+        }
+    }
+
+
     pub(crate) fn remove_alias(&mut self, dir_str: &str) {
         for dir in self.dirs.iter_mut() {
             if dir.name == dir_str {
@@ -645,14 +1114,17 @@ impl App <'_> {
             }
             all_dirs.push(dir.clone());
         }
+        let all_dirs = self.filter_scope(all_dirs);
+        let mut all_dirs = self.valid_or_exit(all_dirs);
+        self.apply_proximity(&mut all_dirs);
         all_dirs.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
-        return all_dirs;
+        all_dirs
     }
 
 
-    pub(crate) fn list_existent(&mut self) {
+    pub(crate) fn list_existent(&mut self, max_num: usize) {
         let all_dirs = self.get_all_dirs(true);
-        self.list_dirs(&all_dirs, 0, 1);
+        self.list_dirs(&all_dirs, max_num, 1);
     }
 
 
@@ -707,6 +1179,423 @@ impl App <'_> {
     }
 
 
+    fn print_corrupt_records(&self) {
+        for record in self.corrupt_records.iter() {
+            println!(
+                "{} {}",
+                self.format("bold", "magenta", format!("line {}:", record.line)),
+                record.error,
+            );
+        }
+    }
+
+
+    pub(crate) fn check_database(&self) {
+        if self.corrupt_records.is_empty() {
+            self.show_exit_detailed_message(
+                "Database OK",
+                format!("{} dirs", self.dirs.len()).as_str()
+            );
+        } else {
+            self.print_corrupt_records();
+            self.show_error(
+                "Corrupt records found",
+                format!(
+                    "{} (run 'cz db repair' to move them out of the database)",
+                    self.corrupt_records.len()
+                ).as_str()
+            );
+        }
+    }
+
+
+    pub(crate) fn repair_database(&self) {
+        // The corrupt records are moved to the '.corrupt' file when the
+        //   database is saved
+        if self.corrupt_records.is_empty() {
+            self.show_exit_message("No corrupt records");
+        } else {
+            self.print_corrupt_records();
+            self.show_exit_detailed_message(
+                "Removed corrupt records",
+                format!("{}", self.corrupt_records.len()).as_str()
+            );
+        }
+    }
+
+
+    /// Apply the retention options to the dirs, and report the removed ones.
+    pub(crate) fn prune_dirs(&mut self, dry_run: bool) {
+        let keep_aliased = self.keep_aliased;
+        let can_remove = |dir: &Directory| !keep_aliased || dir.alias.is_empty();
+        let mut kept_dirs: Vec<Directory> = self.dirs.clone();
+        let mut removed_dirs: Vec<(Directory, &str)> = Vec::new();
+
+        // Aging: scale the counters down so that they add up to 90% of the
+        //   maximum, as z and zoxide do
+        let total_count: i64 = kept_dirs.iter().map(|dir| dir.counter).sum();
+        if self.max_total_count > 0 && total_count > self.max_total_count {
+            let factor = 0.9 * self.max_total_count as f64 / total_count as f64;
+            for dir in kept_dirs.iter_mut() {
+                dir.counter = (dir.counter as f64 * factor) as i64;
+            }
+            let (aged_out, aged): (Vec<Directory>, Vec<Directory>) = kept_dirs
+                .into_iter()
+                .partition(|dir| dir.counter < 1 && can_remove(dir));
+            kept_dirs = aged;
+            for dir in kept_dirs.iter_mut() {
+                dir.counter = dir.counter.max(1);
+            }
+            removed_dirs.extend(aged_out.into_iter().map(|dir| (dir, "aged out")));
+            println!(
+                "{}: {} -> {}",
+                self.format("bold", "green", "Aged visit counters".to_string()),
+                total_count,
+                kept_dirs.iter().map(|dir| dir.counter).sum::<i64>(),
+            );
+        }
+
+        // Age since the last access
+        if self.max_age > 0 {
+            let limit = get_current_seconds() - self.max_age * 60 * 60 * 24;
+            let (too_old, recent): (Vec<Directory>, Vec<Directory>) = kept_dirs
+                .into_iter()
+                .partition(|dir| dir.last_access < limit && can_remove(dir));
+            kept_dirs = recent;
+            removed_dirs.extend(too_old.into_iter().map(|dir| (dir, "too old")));
+        }
+
+        // Number of entries: keep the ones with the highest score
+        if self.max_entries > 0 && kept_dirs.len() > self.max_entries {
+            kept_dirs.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+            let mut remaining = self.max_entries;
+            let mut within_limit: Vec<Directory> = Vec::new();
+            for dir in kept_dirs.into_iter() {
+                if remaining > 0 || !can_remove(&dir) {
+                    remaining = remaining.saturating_sub(1);
+                    within_limit.push(dir);
+                } else {
+                    removed_dirs.push((dir, "over max_entries"));
+                }
+            }
+            kept_dirs = within_limit;
+        }
+
+        for (dir, reason) in removed_dirs.iter() {
+            println!("{} ({})", self.format("bold", "blue", dir.name.clone()), reason);
+        }
+        if dry_run {
+            self.show_exit_detailed_message(
+                "Dirs to prune",
+                format!("{} (dry run, nothing removed)", removed_dirs.len()).as_str()
+            );
+        } else {
+            *self.dirs = kept_dirs;
+            self.show_exit_detailed_message(
+                "Pruned dirs",
+                format!("{}", removed_dirs.len()).as_str()
+            );
+        }
+    }
+
+
+    /// Remove the dirs that match the exclude patterns, e.g., after adding
+    /// new ones; aliased dirs are kept.
+    pub(crate) fn prune_excluded(&mut self, dry_run: bool) {
+        let (excluded, kept): (Vec<Directory>, Vec<Directory>) = self.dirs.clone()
+            .into_iter()
+            .partition(|dir| dir.alias.is_empty() && self.exclusions.is_excluded(&dir.name));
+        for dir in excluded.iter() {
+            println!("{}", self.format("bold", "blue", dir.name.clone()));
+        }
+        if dry_run {
+            self.show_exit_detailed_message(
+                "Excluded dirs to prune",
+                format!("{} (dry run, nothing removed)", excluded.len()).as_str()
+            );
+        } else {
+            *self.dirs = kept;
+            self.show_exit_detailed_message(
+                "Pruned excluded dirs",
+                format!("{}", excluded.len()).as_str()
+            );
+        }
+    }
+
+
+    /// Merge dirs from another source into the database. With the 'sum'
+    /// policy, visit counters are added up; with 'max', the highest one is
+    /// kept (so merging the same dirs twice changes nothing). The most recent
+    /// access is always kept, and the aliases of the other dirs are set. The
+    /// changes are listed.
+    fn merge_dirs(&mut self, other_dirs: Vec<Directory>, policy: &str, dry_run: bool) -> (usize, usize) {
+        let mut index: HashMap<String, usize> = self.dirs.iter()
+            .enumerate()
+            .map(|(i, dir)| (dir.name.clone(), i))
+            .collect();
+        let mut added = 0;
+        let mut updated = 0;
+        for other_dir in other_dirs.into_iter() {
+            let mut alias = String::new();
+            if !other_dir.alias.is_empty() {
+                alias = format!(", alias: {}", other_dir.alias);
+            }
+            // New dirs are excluded as if they were visited, unless aliased
+            if other_dir.alias.is_empty()
+                && !index.contains_key(&other_dir.name)
+                && self.exclusions.is_excluded(&other_dir.name)
+            {
+                println!("{} (excluded)", self.format("bold", "blue", other_dir.name.clone()));
+                continue;
+            }
+            match index.get(&other_dir.name) {
+                Some(i) => {
+                    let counter = self.dirs[*i].counter;
+                    let merged_counter = if policy == "max" {
+                        counter.max(other_dir.counter)
+                    } else {
+                        counter + other_dir.counter
+                    };
+                    println!(
+                        "{} (count: {} -> {}{})",
+                        self.format("bold", "blue", other_dir.name.clone()),
+                        counter,
+                        merged_counter,
+                        alias,
+                    );
+                    if !dry_run {
+                        let dir = &mut self.dirs[*i];
+                        dir.counter = merged_counter;
+                        dir.last_access = dir.last_access.max(other_dir.last_access);
+                    }
+                    updated += 1;
+                }
+                None => {
+                    println!(
+                        "{} (new, count: {}{})",
+                        self.format("bold", "green", other_dir.name.clone()),
+                        other_dir.counter,
+                        alias,
+                    );
+                    if !dry_run {
+                        index.insert(other_dir.name.clone(), self.dirs.len());
+                        let mut dir = other_dir.clone();
+                        dir.alias = String::new();
+                        self.dirs.push(dir);
+                    }
+                    added += 1;
+                }
+            }
+            if !dry_run && !other_dir.alias.is_empty() {
+                self.replace_alias(&other_dir.name, &other_dir.alias);
+            }
+        }
+        self.update_scores();
+        (added, updated)
+    }
+
+
+    pub(crate) fn import_dirs(&mut self, args: &[String]) {
+        let mut format = "";
+        let mut file = "";
+        let mut dry_run = false;
+        let mut i = 2;
+        while i < args.len() {
+            if (args[i] == "--from" || args[i] == "--format") && i + 1 < args.len() {
+                format = args[i + 1].as_str();
+                i += 1;
+            } else if args[i] == "--dry-run" {
+                dry_run = true;
+            } else {
+                file = args[i].as_str();
+            }
+            i += 1;
+        }
+        if format.is_empty() {
+            self.show_error(
+                "No format provided",
+                format!("use --from {}, or --format json", IMPORT_FORMATS.join("|")).as_str()
+            );
+        }
+        if file.is_empty() {
+            self.show_error("No file provided", "");
+        }
+
+        let contents = match fs::read_to_string(file) {
+            Ok(contents) => contents,
+            Err(error) => {
+                self.show_error("Could not read file", error.to_string().as_str());
+                return;
+            }
+        };
+        // A JSON export is restored as it is; the data of other tools is
+        //   added to the existing one
+        let (imported_dirs, skipped, policy) = if format == "json" {
+            match parse_json_dirs(&contents) {
+                Ok(dirs) => (dirs, 0, "max"),
+                Err(error) => {
+                    self.show_error("Invalid JSON", error.as_str());
+                    return;
+                }
+            }
+        } else {
+            match parse_imported(format, &contents) {
+                Ok(imported) => (imported.dirs, imported.skipped, "sum"),
+                Err(error) => {
+                    self.show_error("Invalid format", format!("{}, json", error).as_str());
+                    return;
+                }
+            }
+        };
+        let (added, updated) = self.merge_dirs(imported_dirs, policy, dry_run);
+
+        let details = format!("{} new, {} updated, {} skipped", added, updated, skipped);
+        if dry_run {
+            self.show_exit_detailed_message(
+                "Dirs to import",
+                format!("{} (dry run, nothing imported)", details).as_str()
+            );
+        } else {
+            self.show_exit_detailed_message("Imported dirs", details.as_str());
+        }
+    }
+
+
+    /// Resolve the clashes between the aliases of other dirs and the local
+    /// ones. With the 'other' policy, the other aliases win; with 'local',
+    /// the local ones are kept; with 'report', the local ones are kept and
+    /// the clashes are listed.
+    fn resolve_alias_clashes(&self, other_dirs: &mut [Directory], alias_policy: &str) -> usize {
+        let mut clashes = 0;
+        for other_dir in other_dirs.iter_mut() {
+            if other_dir.alias.is_empty() || alias_policy == "other" {
+                continue;
+            }
+            let clash = self.dirs.iter().find(|dir| {
+                (dir.name == other_dir.name && !dir.alias.is_empty() && dir.alias != other_dir.alias)
+                    || (dir.name != other_dir.name && dir.alias == other_dir.alias)
+            });
+            if let Some(dir) = clash {
+                if alias_policy == "report" {
+                    println!(
+                        "{} {}:{} (local: {}:{})",
+                        self.format("bold", "magenta", "Alias clash:".to_string()),
+                        other_dir.alias,
+                        other_dir.name,
+                        dir.alias,
+                        dir.name,
+                    );
+                }
+                other_dir.alias = String::new();
+                clashes += 1;
+            }
+        }
+        clashes
+    }
+
+
+    pub(crate) fn merge_database(&mut self, args: &[String]) {
+        let mut file = "";
+        let mut policy = "max";
+        let mut alias_policy = "report";
+        let mut rewrites: Vec<(String, String)> = Vec::new();
+        let mut dry_run = false;
+        let mut i = 2;
+        while i < args.len() {
+            let has_value = i + 1 < args.len();
+            if args[i] == "--policy" && has_value {
+                policy = args[i + 1].as_str();
+                i += 1;
+            } else if args[i] == "--aliases" && has_value {
+                alias_policy = args[i + 1].as_str();
+                i += 1;
+            } else if args[i] == "--rewrite" && has_value {
+                match args[i + 1].split_once('=') {
+                    Some((from, to)) if !from.is_empty() => {
+                        rewrites.push((
+                            from.trim_end_matches('/').to_string(),
+                            to.trim_end_matches('/').to_string(),
+                        ));
+                    }
+                    _ => { self.show_error("Invalid rewrite rule", "use --rewrite FROM=TO"); }
+                }
+                i += 1;
+            } else if args[i] == "--dry-run" {
+                dry_run = true;
+            } else {
+                file = args[i].as_str();
+            }
+            i += 1;
+        }
+        if file.is_empty() {
+            self.show_error("No database provided", "");
+        }
+        if policy != "sum" && policy != "max" {
+            self.show_error("Invalid policy", "allowed values: sum, max");
+        }
+        if !["local", "other", "report"].contains(&alias_policy) {
+            self.show_error("Invalid alias policy", "allowed values: local, other, report");
+        }
+
+        let contents = match fs::read_to_string(file) {
+            Ok(contents) => contents,
+            Err(error) => {
+                self.show_error("Could not read file", error.to_string().as_str());
+                return;
+            }
+        };
+        let (mut other_dirs, corrupt_records) = parse_dirs(&contents);
+
+        // Map the paths of the other machine onto local ones (the first
+        //   matching rule is applied)
+        for dir in other_dirs.iter_mut() {
+            for (from, to) in rewrites.iter() {
+                if dir.name == *from || dir.name.starts_with(&format!("{}/", from)) {
+                    dir.name = format!("{}{}", to, &dir.name[from.len()..]);
+                    break;
+                }
+            }
+        }
+
+        let clashes = self.resolve_alias_clashes(&mut other_dirs, alias_policy);
+        let (added, updated) = self.merge_dirs(other_dirs, policy, dry_run);
+
+        let details = format!(
+            "{} new, {} updated, {} alias clashes, {} corrupt records skipped",
+            added, updated, clashes, corrupt_records.len()
+        );
+        if dry_run {
+            self.show_exit_detailed_message(
+                "Dirs to merge",
+                format!("{} (dry run, nothing merged)", details).as_str()
+            );
+        } else {
+            self.show_exit_detailed_message("Merged dirs", details.as_str());
+        }
+    }
+
+
+    pub(crate) fn export(&mut self, args: &[String]) {
+        let mut format = "json";
+        if args.len() > 3 && args[2] == "--format" {
+            format = args[3].as_str();
+        } else if args.len() > 2 {
+            self.show_error(
+                "Invalid arguments",
+                format!("use --format {}", EXPORT_FORMATS.join("|")).as_str()
+            );
+        }
+        // All the recorded dirs, with their own scores: nothing depends on
+        //   the current dir
+        let mut all_dirs = self.dirs.clone();
+        all_dirs.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        match export_dirs(format, &all_dirs) {
+            Ok(output) => { print!("{}", output); }
+            Err(error) => { self.show_error("Invalid format", error.as_str()); }
+        }
+    }
+
+
     pub(crate) fn clear_database(&mut self) {
         self.dirs.clear();
     }
@@ -715,7 +1604,8 @@ impl App <'_> {
     pub(crate) fn remove_alias_interactive(&mut self) {
         let valid_dirs = self.get_valid(
             Vec::new(), true
-        ).unwrap();
+        );
+        let valid_dirs = self.valid_or_exit(valid_dirs);
 
         // Always list dirs
         let dir_name = self.select_valid_dir(valid_dirs, 0).unwrap();
@@ -728,7 +1618,8 @@ impl App <'_> {
     pub(crate) fn interactive_cd(&mut self, args: &[String]) {
         let valid_dirs = self.get_valid(
             Vec::from(&args[1..]), false
-        ).unwrap();
+        );
+        let valid_dirs = self.valid_or_exit(valid_dirs);
 
         // Always list dirs
         let dir_name = self.select_valid_dir(valid_dirs, 0).unwrap();
@@ -739,7 +1630,8 @@ impl App <'_> {
     pub(crate) fn remove_dirs(&mut self, args: &[String]) {
         let valid_dirs = self.get_valid(
             Vec::from(&args[2..]), false
-        ).unwrap();
+        );
+        let valid_dirs = self.valid_or_exit(valid_dirs);
 
         let dir_names = self.select_valid_dirs(valid_dirs, 0).unwrap();
 
@@ -814,7 +1706,8 @@ impl App <'_> {
             }
 
             let dir_name: String; //= String::new();
-            match self.select_valid_dir_no_exit(valid_dirs, usize::MAX, self.nav_start_number, files) {
+            let base_dir = canonicalize_dir_str(&dir_to_read);
+            match self.select_valid_dir_no_exit(valid_dirs, usize::MAX, self.nav_start_number, files, &base_dir) {
                 Ok(dir_string)  => {
                     dir_name = dir_string
                 }
@@ -866,3 +1759,33 @@ impl App <'_> {
         self.direct_cd(dir_to_read);
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::app_from_str;
+
+    /// Config lines using an empty database in a new temporary dir, and
+    /// the dir.
+    fn temp_config(test: &str) -> (String, PathBuf) {
+        let base = env::temp_dir().join(format!("cz-app-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        let database_path = base.join("directories.dir");
+        fs::write(&database_path, "").unwrap();
+        let config = format!("database_path = {}\nfallback = true\n", database_path.to_str().unwrap());
+        (config, base)
+    }
+
+    #[test]
+    fn fallback_needs_substrings() {
+        let (config, base) = temp_config("fallback");
+        let mut dirs = Vec::new();
+        let mut app = app_from_str(&config, &mut dirs);
+        assert_eq!(app.fallback_dir(&[]), None);
+        // Tests run in the root of the crate
+        let src = canonicalize_dir_str("src");
+        assert_eq!(app.fallback_dir(&["src".to_string()]), Some(src));
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
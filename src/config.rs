@@ -1,81 +1,23 @@
 use std::fs;
 use crate::data::Directory;
 use crate::get_home_dir;
-use crate::app::App;
+use crate::app::{App, CURRENT_DIR_POLICIES};
 use crate::strings::DEFAULT_CONFIG;
 use std::path::Path;
 use std::env;
-use crate::app::get_current_seconds;
-
-
-
-fn init_dir_file(database_fn: String, dirs: &mut Vec<Directory>) {
-    // println!("database_fn: {}", database_fn);
-    // Create database_fn if it does not exist
-    if !Path::new(database_fn.as_str()).exists() {
-        let database_fn_parent = Path::new(database_fn.as_str()).parent().unwrap();
-        fs::create_dir_all(database_fn_parent).unwrap_or_else(
-            |e| panic!("Error creating dir: {}", e)
-            );
-        fs::write(database_fn.as_str(), "").unwrap_or_else(
-            |e| panic!("Error creating file: {}", e)
-            );
-    }
-
-    let current_seconds = get_current_seconds();
-    // Current seconds minus 2 months
-    let limit = current_seconds - (60 * 60 * 24 * 30 * 2);
-    // Read database_fn and parse it
-    let db_string = fs::read_to_string(database_fn).unwrap();
-    // println!("db_string:\n{}", db_string);
-    /* The string is like this:
-     * name1
-     * counter1
-     * last_access1
-     * score1
-     * alias1 // alias is optional
-     * ---
-     * name2
-     * counter2
-     * last_access2
-     * score2
-     * ---
-     * ...
-     */
-    let dir_strings = db_string.split("---");
-    for dir_string in dir_strings {
-        let dir_string = dir_string.trim();
-        if dir_string.len() == 0 {
-            continue;
-        }
-        let dir_string = dir_string.split("\n").collect::<Vec<&str>>();
-        let name = dir_string[0].to_string();
-        let counter = dir_string[1].parse::<i64>().unwrap();
-        let last_access = dir_string[2].parse::<i64>().unwrap();
-        if last_access < limit {
-            continue;
-        }
-        let score = 10000.0 * counter as f64 * (3.75 / ((0.0001 * (current_seconds - last_access) as f64 + 1.0) + 0.25));
-        let mut alias = String::from("");
-        if dir_string.len() == 4 {
-            // alias is optional
-            alias = dir_string[3].to_string();
-        }
-        let dir = Directory {
-            name,
-            counter,
-            last_access,
-            score,
-            alias,
-        };
-        dirs.push(dir);
-    }
-}
+use crate::database::{init_dir_file, lock_database};
+use crate::score::ScoreModel;
+use crate::exclude::Exclusions;
+use crate::picker::PICKERS;
+use crate::matcher::{MATCHERS, CASE_SENSITIVITIES};
+
+
 
-// Return either String, i64 or bool
+// Return either String, i64, f64 or bool
 enum Value<'a> {
     String(&'a str),
     I64(i64),
+    F64(f64),
     Bool(bool),
 }
 // Implement Value to String
@@ -84,6 +26,7 @@ impl<'a> ToString for Value<'a> {
         match self {
             Value::String(x) => { x.to_string() }
             Value::I64(x) => { x.to_string() }
+            Value::F64(x) => { x.to_string() }
             Value::Bool(x) => { x.to_string() }
         }
     }
@@ -105,11 +48,20 @@ impl<'a> Value<'a> {
         }
     }
 }
+impl<'a> Value<'a> {
+    fn to_f64(&self) -> f64 {
+        match self {
+            Value::F64(x) => { *x }
+            _ => { panic!("Value is not f64") }
+        }
+    }
+}
 impl<'a> Clone for Value<'a> {
     fn clone(&self) -> Value<'a> {
         match self {
             Value::String(x) => { Value::String(x) }
             Value::I64(x) => { Value::I64(x.clone()) }
+            Value::F64(x) => { Value::F64(*x) }
             Value::Bool(x) => { Value::Bool(x.clone()) }
         }
     }
@@ -118,15 +70,39 @@ impl<'a> Clone for Value<'a> {
 
 
 
+const I64_OPTIONS: [&str; 6] = [
+    "max_results",
+    "nav_start_number",
+    "max_age",
+    "max_entries",
+    "max_total_count",
+    "fallback_dirs",
+];
+const F64_OPTIONS: [&str; 2] = [
+    "score_decay",
+    "proximity_boost",
+];
+const BOOL_OPTIONS: [&str; 6] = [
+    "abs_paths",
+    "compact_paths",
+    "keep_aliased",
+    "fold_unicode",
+    "fallback",
+    "proximity_git_root",
+];
+
+
 // The same with lifetimes
 fn parse_option_string<'a>(option_value: &Option<&'a (String, String)>) -> Value<'a> {
     match option_value {
         Some(x) => {
             let option = &x.0;
             let value = &x.1;
-            if option == "max_results" || option == "nav_start_number" {
+            if I64_OPTIONS.contains(&option.as_str()) {
                 Value::I64(value.parse::<i64>().unwrap())
-            } else if option == "abs_paths" || option == "compact_paths" {
+            } else if F64_OPTIONS.contains(&option.as_str()) {
+                Value::F64(value.parse::<f64>().unwrap())
+            } else if BOOL_OPTIONS.contains(&option.as_str()) {
                 Value::Bool(value.parse::<bool>().unwrap())
             } else {
                 Value::String(&value)
@@ -159,18 +135,71 @@ fn get_option<'a>(user_value: &'a Vec<(String, String)>, default_value: &'a Vec<
 }
 
 
-fn build_app(
-    theme: String,
-    abs_paths: bool,
-    compact_paths: bool,
-    max_results: i64,
-    database_path: String,
-    substring: String,
-    show_files: String,
-    nav_start_number: i64,
-    dirs: &mut Vec<Directory>,
-) -> App {
-    let mut database_path = database_path.clone();
+/// The options of the user config, with the default values of the missing
+/// ones; they are read by name, with the type of the option.
+struct Config {
+    user_value: Vec<(String, String)>,
+    default_value: Vec<(String, String)>,
+}
+
+
+impl Config {
+    fn get(&self, option: &str) -> Value<'_> {
+        get_option(&self.user_value, &self.default_value, option)
+    }
+
+
+    fn string(&self, option: &str) -> String {
+        self.get(option).to_string()
+    }
+
+
+    fn i64(&self, option: &str) -> i64 {
+        self.get(option).to_i64()
+    }
+
+
+    fn f64(&self, option: &str) -> f64 {
+        self.get(option).to_f64()
+    }
+
+
+    fn bool(&self, option: &str) -> bool {
+        self.get(option).to_bool()
+    }
+}
+
+
+fn build_app<'a>(config: &Config, dirs: &'a mut Vec<Directory>) -> App<'a> {
+    let matcher = config.string("matcher");
+    let case_sensitivity = config.string("case_sensitivity");
+    let current_dir = config.string("current_dir");
+    let picker = config.string("picker");
+    if !MATCHERS.contains(&matcher.as_str()) {
+        panic!(
+            "Invalid matcher '{}'; allowed values: {}",
+            matcher, MATCHERS.join(", ")
+        );
+    }
+    if !CASE_SENSITIVITIES.contains(&case_sensitivity.as_str()) {
+        panic!(
+            "Invalid case_sensitivity '{}'; allowed values: {}",
+            case_sensitivity, CASE_SENSITIVITIES.join(", ")
+        );
+    }
+    if !CURRENT_DIR_POLICIES.contains(&current_dir.as_str()) {
+        panic!(
+            "Invalid current_dir '{}'; allowed values: {}",
+            current_dir, CURRENT_DIR_POLICIES.join(", ")
+        );
+    }
+    if !PICKERS.contains(&picker.as_str()) {
+        panic!(
+            "Invalid picker '{}'; allowed values: {}",
+            picker, PICKERS.join(", ")
+        );
+    }
+    let mut database_path = config.string("database_path");
 
     // Replace typical environment variables
     let home_dir = env::var("HOME").unwrap();
@@ -182,18 +211,49 @@ fn build_app(
         |e| panic!("Error creating dir: {}", e)
         );
 
-    init_dir_file(database_path.clone(), dirs);
-    let app = App {
-        theme,
-        abs_paths,
-        compact_paths,
-        max_results: max_results as usize,
+    // Read the database under the lock, so a concurrent write is never seen
+    //   half-done
+    let database_lock = lock_database(database_path.as_str(), false);
+    let corrupt_records = init_dir_file(database_path.clone(), dirs);
+    drop(database_lock);
+    // Keep a copy of the loaded dirs, to know later which changes were made
+    //   by this process
+    let loaded_dirs = dirs.clone();
+
+    let mut app = App {
+        theme: config.string("theme"),
+        abs_paths: config.bool("abs_paths"),
+        compact_paths: config.bool("compact_paths"),
+        max_results: config.i64("max_results") as usize,
         database_path,
-        substring,
-        show_files,
-        nav_start_number: nav_start_number as usize,
+        substring: config.string("substring"),
+        show_files: config.string("show_files"),
+        nav_start_number: config.i64("nav_start_number") as usize,
+        max_age: config.i64("max_age"),
+        max_entries: config.i64("max_entries") as usize,
+        max_total_count: config.i64("max_total_count"),
+        keep_aliased: config.bool("keep_aliased"),
+        score_model: ScoreModel::new(
+            &config.string("score_model"),
+            config.f64("score_decay"),
+            &config.string("score_buckets"),
+        ),
+        matcher,
+        case_sensitivity,
+        fold_unicode: config.bool("fold_unicode"),
+        exclusions: Exclusions::new(&config.string("exclude")),
+        fallback: config.bool("fallback"),
+        fallback_dirs: config.i64("fallback_dirs") as usize,
+        proximity_boost: config.f64("proximity_boost"),
+        proximity_git_root: config.bool("proximity_git_root"),
+        scope: "all".to_string(),
+        current_dir,
+        picker,
         dirs,
+        loaded_dirs,
+        corrupt_records,
     };
+    app.update_scores();
     return app;
 }
 
@@ -208,6 +268,23 @@ fn parse_config(config_string: String) -> Vec<(String, String)> {
      * substring = shortest
      * show_files = none
      * nav_start_number = 1
+     * max_age = 60
+     * max_entries = 0
+     * max_total_count = 0
+     * keep_aliased = true
+     * score_model = frecency
+     * score_decay = 0.0001
+     * score_buckets = 4,2,0.5,0.25
+     * matcher = substring
+     * case_sensitivity = smart
+     * fold_unicode = false
+     * exclude = node_modules:target
+     * fallback = true
+     * fallback_dirs = 10
+     * proximity_boost = 0
+     * proximity_git_root = false
+     * current_dir = demote
+     * picker = builtin
      */
 
     let mut option_values = Vec::new();
@@ -217,33 +294,37 @@ fn parse_config(config_string: String) -> Vec<(String, String)> {
         if config_option.len() == 0 {
             continue;
         }
-        let config_option = config_option.split(" = ").collect::<Vec<&str>>();
-        let option = config_option[0];
-        let value = config_option[1];
-        option_values.push((option.to_string(), value.to_string()));
+        // The value can be empty (e.g., 'exclude =')
+        let (option, value) = match config_option.split_once('=') {
+            Some(option_value) => option_value,
+            None => { panic!("Invalid config line '{}'; 'option = value' expected", config_option); }
+        };
+        option_values.push((option.trim().to_string(), value.trim().to_string()));
     }
     return option_values;
 }
 
 
-pub(crate) fn app_from_config(dirs: &mut Vec<Directory>) -> App {
+pub(crate) fn app_from_config(dirs: &mut Vec<Directory>) -> App<'_> {
     let path = format!("{}/.config/contemporary-z/cz.conf", get_home_dir());
     let config_string = match fs::read_to_string(path) {
         Ok(contents) => { contents }
         Err(_) => { DEFAULT_CONFIG.to_string() }
     };
-    let user_value = parse_config(config_string);
-    let default_value = parse_config(DEFAULT_CONFIG.to_string());
-
-    return build_app(
-        get_option(&user_value, &default_value, "theme").to_string(),
-        get_option(&user_value, &default_value, "abs_paths").to_bool(),
-        get_option(&user_value, &default_value, "compact_paths").to_bool(),
-        get_option(&user_value, &default_value, "max_results").to_i64(),
-        get_option(&user_value, &default_value, "database_path").to_string(),
-        get_option(&user_value, &default_value, "substring").to_string(),
-        get_option(&user_value, &default_value, "show_files").to_string(),
-        get_option(&user_value, &default_value, "nav_start_number").to_i64(),
-        dirs,
-    )
+    let config = Config {
+        user_value: parse_config(config_string),
+        default_value: parse_config(DEFAULT_CONFIG.to_string()),
+    };
+    build_app(&config, dirs)
+}
+
+
+/// Build the app from the given config lines, on top of the default ones.
+#[cfg(test)]
+pub(crate) fn app_from_str<'a>(config_string: &str, dirs: &'a mut Vec<Directory>) -> App<'a> {
+    let config = Config {
+        user_value: parse_config(config_string.to_string()),
+        default_value: parse_config(DEFAULT_CONFIG.to_string()),
+    };
+    build_app(&config, dirs)
 }
@@ -0,0 +1,426 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use crate::data::Directory;
+
+
+
+/// A record of the database that could not be parsed.
+pub(crate) struct CorruptRecord {
+    /// Number of the first line of the record
+    pub(crate) line: usize,
+    pub(crate) error: String,
+    pub(crate) lines: Vec<String>,
+}
+
+
+fn parse_record(lines: &[&str], names: &HashSet<String>) -> Result<Directory, String> {
+    if lines.len() < 3 || lines.len() > 4 {
+        return Err(format!("expected 3 or 4 lines, found {}", lines.len()));
+    }
+    let name = lines[0].to_string();
+    if names.contains(&name) {
+        return Err(format!("duplicated directory '{}'", name));
+    }
+    let counter = match lines[1].parse::<i64>() {
+        Ok(counter) if counter >= 0 => counter,
+        _ => { return Err(format!("invalid counter '{}'", lines[1])); }
+    };
+    let last_access = match lines[2].parse::<i64>() {
+        Ok(last_access) if last_access >= 0 => last_access,
+        _ => { return Err(format!("invalid last access '{}'", lines[2])); }
+    };
+    // alias is optional
+    let alias = match lines.get(3) {
+        Some(alias) => alias.to_string(),
+        None => String::new(),
+    };
+    Ok(Directory {
+        name,
+        counter,
+        last_access,
+        score: 0.0,
+        alias,
+    })
+}
+
+
+/// Parse the contents of the database. Records that cannot be parsed are
+/// returned apart, so the rest of the database can still be used.
+pub(crate) fn parse_dirs(db_string: &str) -> (Vec<Directory>, Vec<CorruptRecord>) {
+    /* The string is like this:
+     * name1
+     * counter1
+     * last_access1
+     * alias1 // alias is optional
+     * ---
+     * name2
+     * counter2
+     * last_access2
+     * ---
+     * ...
+     * Only a line that is exactly '---' ends a record, so names can contain
+     * '---'.
+     */
+    let mut dirs: Vec<Directory> = Vec::new();
+    let mut corrupt_records: Vec<CorruptRecord> = Vec::new();
+    let mut names: HashSet<String> = HashSet::new();
+
+    let mut record: Vec<&str> = Vec::new();
+    let mut record_line = 0;
+    let mut lines = db_string.lines().enumerate().peekable();
+    while let Some((i, line)) = lines.next() {
+        // Names can end with spaces, so only a stray '\r' is removed
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let is_separator = line == "---";
+        if !is_separator && !line.trim().is_empty() {
+            if record.is_empty() {
+                record_line = i + 1;
+            }
+            record.push(line);
+        }
+        // A record also ends at the end of the file, in case the last
+        //   separator was lost
+        if (!is_separator && lines.peek().is_some()) || record.is_empty() {
+            continue;
+        }
+        match parse_record(&record, &names) {
+            Ok(dir) => {
+                names.insert(dir.name.clone());
+                dirs.push(dir);
+            }
+            Err(error) => {
+                corrupt_records.push(CorruptRecord {
+                    line: record_line,
+                    error,
+                    lines: record.iter().map(|l| l.to_string()).collect(),
+                });
+            }
+        }
+        record.clear();
+    }
+    (dirs, corrupt_records)
+}
+
+
+pub(crate) fn init_dir_file(database_fn: String, dirs: &mut Vec<Directory>) -> Vec<CorruptRecord> {
+    // Create database_fn if it does not exist
+    if !Path::new(database_fn.as_str()).exists() {
+        let database_fn_parent = Path::new(database_fn.as_str()).parent().unwrap();
+        fs::create_dir_all(database_fn_parent).unwrap_or_else(
+            |e| panic!("Error creating dir: {}", e)
+            );
+        fs::write(database_fn.as_str(), "").unwrap_or_else(
+            |e| panic!("Error creating file: {}", e)
+            );
+    }
+
+    // Read database_fn and parse it
+    let db_string = fs::read_to_string(database_fn).unwrap_or_else(
+        |e| panic!("Error reading file: {}", e)
+        );
+    let (parsed_dirs, corrupt_records) = parse_dirs(&db_string);
+    // Old dirs are not discarded here, only when the database is pruned.
+    //   Scores are computed by the app, with the configured model
+    dirs.extend(parsed_dirs);
+    corrupt_records
+}
+
+
+/// Append the records to the `.corrupt` file next to the database, so they
+/// can be inspected (and fixed by hand) once removed from the database.
+fn quarantine(database_fn: &str, corrupt_records: &[CorruptRecord]) -> String {
+    let corrupt_fn = format!("{}.corrupt", database_fn);
+    let mut corrupt_string = String::new();
+    for record in corrupt_records.iter() {
+        corrupt_string.push_str(&format!("# line {}: {}\n", record.line, record.error));
+        for line in record.lines.iter() {
+            corrupt_string.push_str(&format!("{}\n", line));
+        }
+        corrupt_string.push_str("---\n");
+    }
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&corrupt_fn)
+        .and_then(|mut corrupt_file| corrupt_file.write_all(corrupt_string.as_bytes()))
+        .unwrap_or_else(|e| panic!("Error writing file: {}", e));
+    corrupt_fn
+}
+
+
+/// Take the advisory lock of the database (a `.lock` file next to it); it is
+/// released when the returned file is dropped.
+pub(crate) fn lock_database(database_fn: &str, exclusive: bool) -> fs::File {
+    let lock_file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(format!("{}.lock", database_fn))
+        .unwrap_or_else(|e| panic!("Error opening lock file: {}", e));
+    let locked = if exclusive {
+        lock_file.lock()
+    } else {
+        lock_file.lock_shared()
+    };
+    locked.unwrap_or_else(|e| panic!("Error locking database: {}", e));
+    lock_file
+}
+
+
+fn dirs_to_string(dirs: &[Directory]) -> String {
+    let mut db_string = String::new();
+    for dir in dirs.iter() {
+        db_string.push_str(&format!("{}\n", dir.name));
+        db_string.push_str(&format!("{}\n", dir.counter));
+        db_string.push_str(&format!("{}\n", dir.last_access));
+        // Score must be a float in the format x.y
+        // db_string.push_str(&format!("{:.10}\n", dir.score));
+        if !dir.alias.is_empty() {
+            db_string.push_str(&format!("{}\n", dir.alias));
+        }
+        db_string.push_str("---\n");
+    }
+    db_string
+}
+
+
+/// Write the dirs to a temporary file in the same dir as the database, and
+/// then rename it into place, so the database is either the old or the new
+/// one, never a partially written file.
+fn write_dir_file(database_fn: &str, dirs: &[Directory]) {
+    let tmp_fn = format!("{}.tmp.{}", database_fn, process::id());
+    let mut tmp_file = fs::File::create(&tmp_fn).unwrap_or_else(
+        |e| panic!("Error writing file: {}", e)
+        );
+    tmp_file.write_all(dirs_to_string(dirs).as_bytes())
+        .and_then(|_| tmp_file.sync_all())
+        .unwrap_or_else(|e| {
+            let _ = fs::remove_file(&tmp_fn);
+            panic!("Error writing file: {}", e)
+        });
+    fs::rename(&tmp_fn, database_fn).unwrap_or_else(|e| {
+        let _ = fs::remove_file(&tmp_fn);
+        panic!("Error writing file: {}", e)
+    });
+}
+
+
+/// Apply the changes made by this process (from `loaded_dirs` to `dirs`) on
+/// top of `stored_dirs`, the database as it is on disk now, which may include
+/// changes made by other processes since it was loaded.
+fn merge_changes(
+    loaded_dirs: &[Directory],
+    dirs: &[Directory],
+    stored_dirs: Vec<Directory>,
+) -> Vec<Directory> {
+    let loaded: HashMap<&str, &Directory> = loaded_dirs.iter()
+        .map(|dir| (dir.name.as_str(), dir))
+        .collect();
+    let current: HashMap<&str, &Directory> = dirs.iter()
+        .map(|dir| (dir.name.as_str(), dir))
+        .collect();
+
+    // Dirs removed by this process
+    let mut merged_dirs: Vec<Directory> = stored_dirs.into_iter()
+        .filter(|dir| {
+            !loaded.contains_key(dir.name.as_str())
+                || current.contains_key(dir.name.as_str())
+        })
+        .collect();
+
+    let mut stored: HashMap<String, usize> = merged_dirs.iter()
+        .enumerate()
+        .map(|(i, dir)| (dir.name.clone(), i))
+        .collect();
+
+    let mut new_aliases: Vec<(String, String)> = Vec::new();
+    for dir in dirs.iter() {
+        let loaded_dir = loaded.get(dir.name.as_str());
+        let stored_dir = stored.get(&dir.name).map(|i| &mut merged_dirs[*i]);
+        let alias_changed = match loaded_dir {
+            Some(loaded_dir) => loaded_dir.alias != dir.alias,
+            None => !dir.alias.is_empty(),
+        };
+        match (loaded_dir, stored_dir) {
+            (Some(loaded_dir), Some(stored_dir)) => {
+                // Add the visits of this process to the stored counter
+                stored_dir.counter += dir.counter - loaded_dir.counter;
+                if stored_dir.counter < 1 {
+                    stored_dir.counter = 1;
+                }
+                stored_dir.last_access = stored_dir.last_access.max(dir.last_access);
+                if alias_changed {
+                    stored_dir.alias = dir.alias.clone();
+                }
+            }
+            (Some(loaded_dir), None) => {
+                // Removed by another process: only keep it if it was used
+                //   by this one
+                if dir.counter != loaded_dir.counter || alias_changed {
+                    stored.insert(dir.name.clone(), merged_dirs.len());
+                    merged_dirs.push(dir.clone());
+                }
+            }
+            (None, Some(stored_dir)) => {
+                // Added by both this process and another one
+                stored_dir.counter += dir.counter;
+                stored_dir.last_access = stored_dir.last_access.max(dir.last_access);
+                if alias_changed {
+                    stored_dir.alias = dir.alias.clone();
+                }
+            }
+            (None, None) => {
+                stored.insert(dir.name.clone(), merged_dirs.len());
+                merged_dirs.push(dir.clone());
+            }
+        }
+        if alias_changed && !dir.alias.is_empty() {
+            new_aliases.push((dir.name.clone(), dir.alias.clone()));
+        }
+    }
+
+    // Aliases are unique: the ones set by this process win
+    for (name, alias) in new_aliases.iter() {
+        for dir in merged_dirs.iter_mut() {
+            if &dir.alias == alias && &dir.name != name {
+                dir.alias = String::new();
+            }
+        }
+    }
+
+    merged_dirs
+}
+
+
+/// Save the changes made to the dirs, so concurrent sessions do not lose
+/// each other's changes.
+///
+/// The lock is not held for the whole session: the database is read at
+/// startup under a shared lock, which is released before any prompt, so a
+/// session waiting for input does not block the others. Here, under the
+/// exclusive lock, the database is read again and the changes of this
+/// session (from `loaded_dirs` to `dirs`) are merged into its current
+/// contents (see `merge_changes`) before writing it.
+pub(crate) fn save(database_fn: &str, loaded_dirs: &[Directory], dirs: &[Directory]) {
+    let _database_lock = lock_database(database_fn, true);
+    let mut stored_dirs: Vec<Directory> = Vec::new();
+    let corrupt_records = init_dir_file(database_fn.to_string(), &mut stored_dirs);
+    // The database is rewritten from the parsed dirs only, so move the records
+    //   that could not be parsed out of the way first
+    if !corrupt_records.is_empty() {
+        let corrupt_fn = quarantine(database_fn, &corrupt_records);
+        eprintln!(
+            "Moved {} corrupt record(s) of the database to '{}'",
+            corrupt_records.len(), corrupt_fn
+        );
+    }
+    let merged_dirs = merge_changes(loaded_dirs, dirs, stored_dirs);
+    write_dir_file(database_fn, &merged_dirs);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str, counter: i64, last_access: i64, alias: &str) -> Directory {
+        Directory {
+            name: name.to_string(),
+            counter,
+            last_access,
+            score: 0.0,
+            alias: alias.to_string(),
+        }
+    }
+
+    fn find<'a>(dirs: &'a [Directory], name: &str) -> &'a Directory {
+        dirs.iter().find(|dir| dir.name == name).unwrap()
+    }
+
+    #[test]
+    fn parse_names_with_separator() {
+        let (dirs, corrupt) = parse_dirs("/tmp/a---b\n3\n10\n---\n/tmp/---\n1\n20\nx\n---\n");
+        assert!(corrupt.is_empty());
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(dirs[0].name, "/tmp/a---b");
+        assert_eq!(dirs[1].name, "/tmp/---");
+        assert_eq!(dirs[1].alias, "x");
+    }
+
+    #[test]
+    fn parse_without_final_separator() {
+        let (dirs, corrupt) = parse_dirs("/a\n1\n10\n---\n/b\n2\n20");
+        assert!(corrupt.is_empty());
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(dirs[1].name, "/b");
+        assert_eq!(dirs[1].counter, 2);
+    }
+
+    #[test]
+    fn parse_keeps_trailing_spaces() {
+        let (dirs, _) = parse_dirs("/tmp/name \t\n1\n10\n---\n/tmp/crlf\r\n1\n10\r\n---\r\n");
+        assert_eq!(dirs[0].name, "/tmp/name \t");
+        assert_eq!(dirs[1].name, "/tmp/crlf");
+    }
+
+    #[test]
+    fn parse_reports_corrupt_records() {
+        let db = "/a\n1\n10\n---\n/b\nx\n20\n---\n/c\n3\n30\n---\n/a\n1\n10\n---\n";
+        let (dirs, corrupt) = parse_dirs(db);
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(corrupt.len(), 2);
+        assert_eq!(corrupt[0].line, 5);
+        assert_eq!(corrupt[0].lines, vec!["/b", "x", "20"]);
+        assert!(corrupt[0].error.contains("invalid counter"));
+        assert_eq!(corrupt[1].line, 13);
+        assert!(corrupt[1].error.contains("duplicated"));
+    }
+
+    #[test]
+    fn merge_adds_visits_of_both_processes() {
+        let loaded = vec![dir("/a", 5, 10, "")];
+        let current = vec![dir("/a", 7, 30, "")];
+        let stored = vec![dir("/a", 6, 20, "")];
+        let merged = merge_changes(&loaded, &current, stored);
+        assert_eq!(find(&merged, "/a").counter, 8);
+        assert_eq!(find(&merged, "/a").last_access, 30);
+    }
+
+    #[test]
+    fn merge_keeps_dirs_added_by_other_process() {
+        let loaded = vec![dir("/a", 1, 10, "")];
+        let current = vec![dir("/a", 1, 10, ""), dir("/b", 1, 20, "")];
+        let stored = vec![dir("/a", 1, 10, ""), dir("/c", 1, 15, "")];
+        let merged = merge_changes(&loaded, &current, stored);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(find(&merged, "/b").counter, 1);
+        assert_eq!(find(&merged, "/c").counter, 1);
+    }
+
+    #[test]
+    fn merge_removals() {
+        // '/a' removed by this process, '/b' (unused here) and '/c' (used
+        //   here) by another one
+        let loaded = vec![dir("/a", 1, 10, ""), dir("/b", 1, 10, ""), dir("/c", 1, 10, "")];
+        let current = vec![dir("/b", 1, 10, ""), dir("/c", 2, 20, "")];
+        let stored = vec![dir("/a", 1, 10, "")];
+        let merged = merge_changes(&loaded, &current, stored);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].name, "/c");
+        assert_eq!(merged[0].counter, 2);
+    }
+
+    #[test]
+    fn merge_keeps_aliases_unique() {
+        let loaded = vec![dir("/a", 1, 10, ""), dir("/b", 1, 10, "")];
+        let current = vec![dir("/a", 1, 10, "w"), dir("/b", 1, 10, "")];
+        let stored = vec![dir("/a", 1, 10, ""), dir("/b", 1, 10, "w")];
+        let merged = merge_changes(&loaded, &current, stored);
+        assert_eq!(find(&merged, "/a").alias, "w");
+        assert_eq!(find(&merged, "/b").alias, "");
+    }
+}
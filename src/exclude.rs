@@ -0,0 +1,132 @@
+use std::fs;
+use crate::app::get_home_dir;
+use crate::glob;
+use crate::regex::Regex;
+
+
+
+/// Glob patterns of the directories that are not recorded, from the
+/// 'exclude' option and from the '.czignore' file.
+///
+/// Like in '.gitignore', a pattern without '/' (e.g., 'node_modules') matches
+/// any component of the path; otherwise (e.g., '/tmp/*' or '~/build'), it
+/// matches the whole path. Subdirectories of excluded dirs are excluded too.
+pub(crate) struct Exclusions {
+    component_patterns: Vec<Regex>,
+    path_patterns: Vec<Regex>,
+}
+
+
+pub(crate) fn ignore_file_path() -> String {
+    format!("{}/.config/contemporary-z/.czignore", get_home_dir())
+}
+
+
+impl Exclusions {
+    /// `exclude` holds patterns separated by ':'; the ignore file, one
+    /// pattern per line ('#' starts a comment).
+    pub(crate) fn new(exclude: &str) -> Exclusions {
+        let ignored = fs::read_to_string(ignore_file_path()).unwrap_or_default();
+        let patterns = exclude.split(':')
+            .chain(ignored.lines().filter(|line| !line.trim_start().starts_with('#')));
+        Exclusions::from_patterns(patterns)
+    }
+
+
+    /// Invalid patterns are reported and skipped, so that a typo in the
+    /// configuration does not prevent using cz.
+    fn from_patterns<'a>(patterns: impl Iterator<Item = &'a str>) -> Exclusions {
+        let mut exclusions = Exclusions {
+            component_patterns: Vec::new(),
+            path_patterns: Vec::new(),
+        };
+        for pattern in patterns {
+            let pattern = pattern.trim();
+            let pattern = if pattern.len() > 1 { pattern.trim_end_matches('/') } else { pattern };
+            if pattern.is_empty() {
+                continue;
+            }
+            let pattern = match pattern.strip_prefix('~') {
+                Some(rest) => format!("{}{}", get_home_dir(), rest),
+                None => pattern.to_string(),
+            };
+            let regex = match glob::compile(&pattern) {
+                Ok(regex) => regex,
+                Err(error) => {
+                    eprintln!("Invalid exclude pattern '{}': {}", pattern, error);
+                    continue;
+                }
+            };
+            if pattern.contains('/') {
+                exclusions.path_patterns.push(regex);
+            } else {
+                exclusions.component_patterns.push(regex);
+            }
+        }
+        exclusions
+    }
+
+
+    pub(crate) fn is_excluded(&self, dir_name: &str) -> bool {
+        let components = dir_name.split('/').filter(|component| !component.is_empty());
+        let mut path = String::new();
+        for component in components {
+            if self.component_patterns.iter().any(|regex| regex.is_match(component)) {
+                return true;
+            }
+            path.push('/');
+            path.push_str(component);
+            if self.path_patterns.iter().any(|regex| regex.is_match(&path)) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exclusions(patterns: &[&str]) -> Exclusions {
+        Exclusions::from_patterns(patterns.iter().copied())
+    }
+
+    #[test]
+    fn component_patterns() {
+        let exclusions = exclusions(&["node_modules", "*.egg-info"]);
+        assert!(exclusions.is_excluded("/home/user/web/node_modules"));
+        assert!(exclusions.is_excluded("/node_modules"));
+        assert!(exclusions.is_excluded("/src/cz.egg-info"));
+        assert!(!exclusions.is_excluded("/home/user/node_modules_old"));
+        assert!(!exclusions.is_excluded("/home/user/web"));
+    }
+
+    #[test]
+    fn path_patterns() {
+        let exclusions = exclusions(&["/tmp/*", "/var/cache/"]);
+        assert!(exclusions.is_excluded("/tmp/build"));
+        assert!(exclusions.is_excluded("/var/cache"));
+        // The whole path must match, not only a component
+        assert!(!exclusions.is_excluded("/tmp"));
+        assert!(!exclusions.is_excluded("/home/tmp/build"));
+        assert!(!exclusions.is_excluded("/home/var/cache"));
+    }
+
+    #[test]
+    fn subdirectories_are_excluded() {
+        let exclusions = exclusions(&["/tmp/*", "target"]);
+        assert!(exclusions.is_excluded("/tmp/build/debug/deps"));
+        assert!(exclusions.is_excluded("/src/cz/target/debug"));
+        assert!(!exclusions.is_excluded("/src/cz/targets/debug"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_skipped() {
+        let exclusions = exclusions(&["[z-a]", "", "build"]);
+        assert_eq!(exclusions.component_patterns.len(), 1);
+        assert!(exclusions.path_patterns.is_empty());
+        assert!(exclusions.is_excluded("/src/build"));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::data::Directory;
+use crate::json::escape_string;
+
+
+
+pub(crate) const EXPORT_FORMATS: [&str; 3] = ["json", "csv", "tsv"];
+
+const FIELDS: [&str; 5] = ["name", "counter", "last_access", "score", "alias"];
+
+
+fn to_json(dirs: &[Directory]) -> String {
+    let mut output = String::from("[\n");
+    for (i, dir) in dirs.iter().enumerate() {
+        output.push_str(&format!(
+            "  {{\"name\": {}, \"counter\": {}, \"last_access\": {}, \"score\": {}, \"alias\": {}}}",
+            escape_string(&dir.name),
+            dir.counter,
+            dir.last_access,
+            dir.score,
+            escape_string(&dir.alias),
+        ));
+        if i + 1 < dirs.len() {
+            output.push(',');
+        }
+        output.push('\n');
+    }
+    output.push_str("]\n");
+    output
+}
+
+
+/// Quote the field if needed (RFC 4180).
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+
+/// TSV fields cannot be quoted, so tabs and newlines are escaped.
+fn tsv_field(field: &str) -> String {
+    field.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+
+fn to_table(dirs: &[Directory], separator: &str, escape: fn(&str) -> String) -> String {
+    let mut output = format!("{}\n", FIELDS.join(separator));
+    for dir in dirs.iter() {
+        let fields = [
+            escape(&dir.name),
+            dir.counter.to_string(),
+            dir.last_access.to_string(),
+            dir.score.to_string(),
+            escape(&dir.alias),
+        ];
+        output.push_str(&format!("{}\n", fields.join(separator)));
+    }
+    output
+}
+
+
+pub(crate) fn export_dirs(format: &str, dirs: &[Directory]) -> Result<String, String> {
+    match format {
+        "json" => Ok(to_json(dirs)),
+        "csv" => Ok(to_table(dirs, ",", csv_field)),
+        "tsv" => Ok(to_table(dirs, "\t", tsv_field)),
+        _ => Err(format!("allowed values: {}", EXPORT_FORMATS.join(", "))),
+    }
+}
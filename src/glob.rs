@@ -0,0 +1,114 @@
+use crate::regex::Regex;
+
+
+
+/// Translate a glob into an equivalent regex, anchored at both ends: '*'
+/// matches within a path component, '**' across components, '?' any
+/// character but '/', and '[...]' ('[!...]' to negate) a class.
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::from("^");
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    // '**/' also matches no directory at all
+                    regex.push_str("(?:.*/)?");
+                    i += 1;
+                } else {
+                    regex.push_str(".*");
+                }
+                i += 1;
+            }
+            '*' => { regex.push_str("[^/]*"); }
+            '?' => { regex.push_str("[^/]"); }
+            '[' => {
+                match chars[i + 1..].iter().skip(1).position(|c| *c == ']') {
+                    Some(length) => {
+                        let class: String = chars[i + 1..i + 2 + length].iter().collect();
+                        let class = match class.strip_prefix('!') {
+                            Some(negated) => format!("^{}", negated),
+                            None => class,
+                        };
+                        regex.push_str(&format!("[{}]", class));
+                        i += length + 2;
+                    }
+                    // Without a closing ']', it is a literal
+                    None => { regex.push_str("\\["); }
+                }
+            }
+            c => {
+                if "\\.^$|()[]{}+*?".contains(c) {
+                    regex.push('\\');
+                }
+                regex.push(c);
+            }
+        }
+        i += 1;
+    }
+    regex.push('$');
+    regex
+}
+
+
+pub(crate) fn compile(glob: &str) -> Result<Regex, String> {
+    Regex::new(&glob_to_regex(glob))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(glob: &str, text: &str) -> bool {
+        compile(glob).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn translation() {
+        assert_eq!(glob_to_regex("a*b?"), "^a[^/]*b[^/]$");
+        assert_eq!(glob_to_regex("**/x"), "^(?:.*/)?x$");
+        assert_eq!(glob_to_regex("a.(b)+"), "^a\\.\\(b\\)\\+$");
+        assert_eq!(glob_to_regex("[!a-c]"), "^[^a-c]$");
+    }
+
+    #[test]
+    fn star_within_a_component() {
+        assert!(is_match("~/work/*/api", "~/work/shop/api"));
+        assert!(!is_match("~/work/*/api", "~/work/shop/v2/api"));
+        assert!(!is_match("~/work/*/api", "~/work/shop/api/old"));
+        assert!(is_match("/tmp/*", "/tmp/build"));
+        assert!(!is_match("/tmp/*", "/tmp/build/x"));
+        assert!(is_match("*.d", "conf.d"));
+    }
+
+    #[test]
+    fn double_star_across_components() {
+        assert!(is_match("~/mnt/**", "~/mnt/disk/photos"));
+        assert!(is_match("~/mnt/**", "~/mnt/"));
+        assert!(is_match("/src/**/api", "/src/api"));
+        assert!(is_match("/src/**/api", "/src/a/b/api"));
+        assert!(!is_match("/src/**/api", "/src/a/b/api2"));
+    }
+
+    #[test]
+    fn question_mark_and_classes() {
+        assert!(is_match("v?", "v2"));
+        assert!(!is_match("v?", "v/"));
+        assert!(!is_match("v?", "v10"));
+        assert!(is_match("[nt]*", "node_modules"));
+        assert!(is_match("[nt]*", "target"));
+        assert!(!is_match("[!nt]*", "target"));
+        assert!(is_match("[!nt]*", "build"));
+        // Without a closing ']', '[' is a literal
+        assert!(is_match("a[b", "a[b"));
+    }
+
+    #[test]
+    fn literal_names() {
+        assert!(is_match("node_modules", "node_modules"));
+        assert!(!is_match("node_modules", "my_node_modules"));
+        assert!(is_match("a+b(c)", "a+b(c)"));
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::app::{get_current_seconds, get_home_dir};
+use crate::data::Directory;
+use crate::json;
+use crate::json::JsonValue;
+use crate::utils::canonicalize_dir_str;
+
+
+
+pub(crate) const IMPORT_FORMATS: [&str; 6] = [
+    "z", "zoxide", "autojump", "fasd", "bash-history", "zsh-history",
+];
+
+
+/// Directories read from the data of another tool, with the number of
+/// entries that could not be used.
+pub(crate) struct ImportedDirs {
+    pub(crate) dirs: Vec<Directory>,
+    pub(crate) skipped: usize,
+}
+
+
+fn new_dir(name: &str, rank: f64, last_access: i64) -> Directory {
+    Directory {
+        name: name.to_string(),
+        counter: (rank.round() as i64).max(1),
+        last_access,
+        score: 0.0,
+        alias: String::new(),
+    }
+}
+
+
+/// Accumulate the dir, if it is an existing directory; it is stored with its
+/// canonical name, as cz does.
+fn add_dir(imported: &mut ImportedDirs, index: &mut HashMap<String, usize>, dir: Directory) {
+    let name = match dir.name.strip_prefix('~') {
+        Some(rest) => format!("{}{}", get_home_dir(), rest),
+        None => dir.name.clone(),
+    };
+    if !Path::new(&name).is_dir() {
+        imported.skipped += 1;
+        return;
+    }
+    let name = canonicalize_dir_str(&name);
+    match index.get(&name) {
+        Some(i) => {
+            let existing = &mut imported.dirs[*i];
+            existing.counter += dir.counter;
+            existing.last_access = existing.last_access.max(dir.last_access);
+        }
+        None => {
+            index.insert(name.clone(), imported.dirs.len());
+            imported.dirs.push(Directory { name, ..dir });
+        }
+    }
+}
+
+
+/// 'path|rank|time' lines, used by z and fasd.
+fn parse_z(contents: &str, imported: &mut ImportedDirs, index: &mut HashMap<String, usize>) {
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        // The path can contain '|', so split from the right
+        let fields: Vec<&str> = line.rsplitn(3, '|').collect();
+        if fields.len() != 3 {
+            imported.skipped += 1;
+            continue;
+        }
+        match (fields[1].parse::<f64>(), fields[0].parse::<i64>()) {
+            (Ok(rank), Ok(time)) => { add_dir(imported, index, new_dir(fields[2], rank, time)); }
+            _ => { imported.skipped += 1; }
+        }
+    }
+}
+
+
+/// 'score path' lines, as printed by 'zoxide query --list --score'.
+fn parse_zoxide(contents: &str, imported: &mut ImportedDirs, index: &mut HashMap<String, usize>) {
+    let current_seconds = get_current_seconds();
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let line = line.trim_start();
+        let (score, path) = match line.split_once(' ') {
+            Some((score, path)) => (score, path.trim_start()),
+            None => {
+                imported.skipped += 1;
+                continue;
+            }
+        };
+        match score.parse::<f64>() {
+            Ok(score) => { add_dir(imported, index, new_dir(path, score, current_seconds)); }
+            Err(_) => { imported.skipped += 1; }
+        }
+    }
+}
+
+
+/// 'weight<TAB>path' lines, as stored in autojump.txt.
+fn parse_autojump(contents: &str, imported: &mut ImportedDirs, index: &mut HashMap<String, usize>) {
+    let current_seconds = get_current_seconds();
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let (weight, path) = match line.split_once('\t') {
+            Some(fields) => fields,
+            None => {
+                imported.skipped += 1;
+                continue;
+            }
+        };
+        match weight.parse::<f64>() {
+            Ok(weight) => { add_dir(imported, index, new_dir(path, weight, current_seconds)); }
+            Err(_) => { imported.skipped += 1; }
+        }
+    }
+}
+
+
+/// Return the target of a 'cd' or 'pushd' command, if it is an absolute path
+/// (or relative to the home dir); relative paths cannot be resolved.
+fn cd_target(command: &str) -> Option<String> {
+    // Ignore the rest of compound commands
+    let command = command.split([';', '&', '|']).next().unwrap_or("");
+    let mut words = command.split_whitespace();
+    match words.next() {
+        Some("cd") | Some("pushd") => {}
+        _ => { return None; }
+    }
+    let target: Vec<&str> = words.collect();
+    let target = target.join(" ");
+    let target = target.trim_matches(['"', '\'']);
+    if target.starts_with('/') || target.starts_with("~/") || target == "~" {
+        Some(target.to_string())
+    } else {
+        None
+    }
+}
+
+
+/// Bash history; the '#<time>' lines written when HISTTIMEFORMAT is set are
+/// used as access times.
+fn parse_bash_history(contents: &str, imported: &mut ImportedDirs, index: &mut HashMap<String, usize>) {
+    let mut time = get_current_seconds();
+    for line in contents.lines() {
+        if let Some(timestamp) = line.strip_prefix('#') {
+            if let Ok(timestamp) = timestamp.parse::<i64>() {
+                time = timestamp;
+                continue;
+            }
+        }
+        if let Some(target) = cd_target(line) {
+            add_dir(imported, index, new_dir(&target, 1.0, time));
+        }
+    }
+}
+
+
+/// Zsh history, either plain or in the extended format
+/// (': <time>:<duration>;<command>').
+fn parse_zsh_history(contents: &str, imported: &mut ImportedDirs, index: &mut HashMap<String, usize>) {
+    let current_seconds = get_current_seconds();
+    for line in contents.lines() {
+        let mut time = current_seconds;
+        let mut command = line;
+        if let Some(extended) = line.strip_prefix(": ") {
+            if let Some((metadata, extended_command)) = extended.split_once(';') {
+                if let Some(timestamp) = metadata.split(':').next() {
+                    time = timestamp.trim().parse::<i64>().unwrap_or(current_seconds);
+                }
+                command = extended_command;
+            }
+        }
+        if let Some(target) = cd_target(command) {
+            add_dir(imported, index, new_dir(&target, 1.0, time));
+        }
+    }
+}
+
+
+/// Read the directories from the contents of a data file of another tool.
+pub(crate) fn parse_imported(format: &str, contents: &str) -> Result<ImportedDirs, String> {
+    let mut imported = ImportedDirs { dirs: Vec::new(), skipped: 0 };
+    let mut index: HashMap<String, usize> = HashMap::new();
+    match format {
+        "z" | "fasd" => { parse_z(contents, &mut imported, &mut index); }
+        "zoxide" => { parse_zoxide(contents, &mut imported, &mut index); }
+        "autojump" => { parse_autojump(contents, &mut imported, &mut index); }
+        "bash-history" => { parse_bash_history(contents, &mut imported, &mut index); }
+        "zsh-history" => { parse_zsh_history(contents, &mut imported, &mut index); }
+        _ => {
+            return Err(format!("allowed values: {}", IMPORT_FORMATS.join(", ")));
+        }
+    }
+    Ok(imported)
+}
+
+
+fn json_i64(value: Option<&JsonValue>) -> Option<i64> {
+    match value {
+        Some(JsonValue::Number(number)) if number.fract() == 0.0 => Some(*number as i64),
+        _ => None,
+    }
+}
+
+
+/// Read the dirs of a database exported with 'cz export --format json'.
+pub(crate) fn parse_json_dirs(contents: &str) -> Result<Vec<Directory>, String> {
+    let items = match json::parse(contents)? {
+        JsonValue::Array(items) => items,
+        _ => { return Err("expected an array of directories".to_string()); }
+    };
+    let mut dirs: Vec<Directory> = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let name = match item.get("name") {
+            Some(JsonValue::String(name)) if !name.is_empty() => name.clone(),
+            _ => { return Err(format!("directory {}: invalid or missing 'name'", i + 1)); }
+        };
+        let counter = match json_i64(item.get("counter")) {
+            Some(counter) if counter >= 0 => counter,
+            _ => { return Err(format!("directory {}: invalid or missing 'counter'", i + 1)); }
+        };
+        let last_access = match json_i64(item.get("last_access")) {
+            Some(last_access) => last_access,
+            None => { return Err(format!("directory {}: invalid or missing 'last_access'", i + 1)); }
+        };
+        // The score is computed, and the alias is optional
+        let alias = match item.get("alias") {
+            Some(JsonValue::String(alias)) => alias.clone(),
+            None | Some(JsonValue::Null) => String::new(),
+            _ => { return Err(format!("directory {}: invalid 'alias'", i + 1)); }
+        };
+        dirs.push(Directory {
+            name,
+            counter,
+            last_access,
+            score: 0.0,
+            alias,
+        });
+    }
+    Ok(dirs)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::export_dirs;
+
+    fn dir(name: &str, counter: i64, last_access: i64, alias: &str) -> Directory {
+        Directory {
+            name: name.to_string(),
+            counter,
+            last_access,
+            score: 1.5,
+            alias: alias.to_string(),
+        }
+    }
+
+    /// Create the subdirs in a new temporary dir, and return its canonical name.
+    fn temp_dirs(test: &str, subdirs: &[&str]) -> String {
+        let base = std::env::temp_dir()
+            .join(format!("cz-import-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        for subdir in subdirs {
+            std::fs::create_dir_all(base.join(subdir)).unwrap();
+        }
+        std::fs::create_dir_all(&base).unwrap();
+        canonicalize_dir_str(base.to_str().unwrap())
+    }
+
+    #[test]
+    fn z_paths_with_pipes() {
+        let base = temp_dirs("z", &["a|b", "plain"]);
+        let contents = format!(
+            "{base}/a|b|3|1700000000\n{base}/plain|2.6|1700000500\n\
+             {base}/missing|1|1700000000\nnot a z line\n{base}/plain|x|1\n",
+            base = base,
+        );
+        let imported = parse_imported("z", &contents).unwrap();
+        assert_eq!(imported.dirs.len(), 2);
+        assert_eq!(imported.dirs[0].name, format!("{}/a|b", base));
+        assert_eq!(imported.dirs[0].counter, 3);
+        assert_eq!(imported.dirs[0].last_access, 1700000000);
+        assert_eq!(imported.dirs[1].name, format!("{}/plain", base));
+        assert_eq!(imported.dirs[1].counter, 3);
+        // Missing dir, line without fields, and invalid rank
+        assert_eq!(imported.skipped, 3);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn zsh_extended_history() {
+        let base = temp_dirs("zsh", &["src", "docs"]);
+        let contents = format!(
+            ": 1700000000:0;cd {base}/src\n: 1700000100:3;ls -la\n\
+             : 1700000200:0;cd {base}/src && make\ncd {base}/docs\n\
+             : 1700000300:0;cd relative/path\n",
+            base = base,
+        );
+        let imported = parse_imported("zsh-history", &contents).unwrap();
+        assert_eq!(imported.dirs.len(), 2);
+        assert_eq!(imported.dirs[0].name, format!("{}/src", base));
+        assert_eq!(imported.dirs[0].counter, 2);
+        assert_eq!(imported.dirs[0].last_access, 1700000200);
+        assert_eq!(imported.dirs[1].name, format!("{}/docs", base));
+        assert!(imported.dirs[1].last_access > 1700000300);
+        assert_eq!(imported.skipped, 0);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn bash_history_timestamps() {
+        let base = temp_dirs("bash", &["src", "my dir"]);
+        let contents = format!(
+            "#1700000000\ncd {base}/src\n#1700000100\npushd \"{base}/my dir\"\n\
+             #1700000200\ncd ..\ncd src\n#not-a-time\ncd {base}/missing\n",
+            base = base,
+        );
+        let imported = parse_imported("bash-history", &contents).unwrap();
+        assert_eq!(imported.dirs.len(), 2);
+        assert_eq!(imported.dirs[0].name, format!("{}/src", base));
+        assert_eq!(imported.dirs[0].last_access, 1700000000);
+        assert_eq!(imported.dirs[1].name, format!("{}/my dir", base));
+        assert_eq!(imported.dirs[1].last_access, 1700000100);
+        // Only the missing dir; relative targets are ignored
+        assert_eq!(imported.skipped, 1);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn relative_cd_targets() {
+        assert_eq!(cd_target("cd /usr/lib"), Some("/usr/lib".to_string()));
+        assert_eq!(cd_target("cd ~/src; make"), Some("~/src".to_string()));
+        assert_eq!(cd_target("pushd ~"), Some("~".to_string()));
+        assert_eq!(cd_target("cd src"), None);
+        assert_eq!(cd_target("cd ../lib"), None);
+        assert_eq!(cd_target("cd"), None);
+        assert_eq!(cd_target("echo cd /usr"), None);
+    }
+
+    #[test]
+    fn zoxide_and_autojump() {
+        let base = temp_dirs("other", &["a b"]);
+        let zoxide = format!("  12.5 {base}/a b\nnoscore\nx {base}/a b\n", base = base);
+        let imported = parse_imported("zoxide", &zoxide).unwrap();
+        assert_eq!(imported.dirs.len(), 1);
+        assert_eq!(imported.dirs[0].counter, 13);
+        assert_eq!(imported.skipped, 2);
+        let autojump = format!("4.0\t{base}/a b\n2.0\t{base}/missing\n", base = base);
+        let imported = parse_imported("autojump", &autojump).unwrap();
+        assert_eq!(imported.dirs.len(), 1);
+        assert_eq!(imported.dirs[0].counter, 4);
+        assert_eq!(imported.skipped, 1);
+        assert!(parse_imported("unknown", "").is_err());
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let dirs = vec![
+            dir("/home/user/src", 12, 1700000000, "src"),
+            dir("/tmp/with \"quotes\" and \\", 1, 0, ""),
+            dir("/tmp/tab\tnewline\n", 3, 5, ""),
+            dir("/tmp/ünïcödé/😀", 7, 42, "e"),
+        ];
+        let exported = export_dirs("json", &dirs).unwrap();
+        let imported = parse_json_dirs(&exported).unwrap();
+        assert_eq!(imported.len(), dirs.len());
+        for (original, imported) in dirs.iter().zip(imported.iter()) {
+            assert_eq!(imported.name, original.name);
+            assert_eq!(imported.counter, original.counter);
+            assert_eq!(imported.last_access, original.last_access);
+            assert_eq!(imported.alias, original.alias);
+        }
+    }
+
+    #[test]
+    fn json_from_other_tools() {
+        let contents = r#"[{"name": "/tmp/\uD83D\uDE00", "counter": 2, "last_access": 10, "alias": null}]"#;
+        let imported = parse_json_dirs(contents).unwrap();
+        assert_eq!(imported[0].name, "/tmp/😀");
+        assert_eq!(imported[0].alias, "");
+    }
+
+    #[test]
+    fn json_invalid_dirs() {
+        assert!(parse_json_dirs("{}").is_err());
+        let missing_counter = r#"[{"name": "/a", "last_access": 1}]"#;
+        assert_eq!(
+            parse_json_dirs(missing_counter).err().unwrap(),
+            "directory 1: invalid or missing 'counter'"
+        );
+        assert!(parse_json_dirs(r#"[{"name": "", "counter": 1, "last_access": 1}]"#).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+/// Minimal JSON support (no external dependencies), enough to export and
+/// import the database.
+#[allow(dead_code)]
+pub(crate) enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+
+impl JsonValue {
+    pub(crate) fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => {
+                members.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+            }
+            _ => None,
+        }
+    }
+}
+
+
+pub(crate) fn escape_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    text: &'a str,
+}
+
+
+impl<'a> Parser<'a> {
+    fn error(&mut self, message: &str) -> String {
+        let position = match self.chars.peek() {
+            Some((i, _)) => *i,
+            None => self.text.len(),
+        };
+        // Report the position as line:column
+        let before = &self.text[..position];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+        format!("{} at {}:{}", message, line, column)
+    }
+
+
+    fn skip_whitespace(&mut self) {
+        while let Some((_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some((_, c)) if *c == expected => {
+                self.chars.next();
+                Ok(())
+            }
+            _ => Err(self.error(&format!("expected '{}'", expected))),
+        }
+    }
+
+
+    fn parse_literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, String> {
+        for expected in literal.chars() {
+            match self.chars.next() {
+                Some((_, c)) if c == expected => {}
+                _ => { return Err(self.error("invalid literal")); }
+            }
+        }
+        Ok(value)
+    }
+
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => { return Ok(string); }
+                Some((_, '\\')) => {
+                    let escaped = match self.chars.next() {
+                        Some((_, '"')) => '"',
+                        Some((_, '\\')) => '\\',
+                        Some((_, '/')) => '/',
+                        Some((_, 'b')) => '\u{8}',
+                        Some((_, 'f')) => '\u{c}',
+                        Some((_, 'n')) => '\n',
+                        Some((_, 'r')) => '\r',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'u')) => self.parse_unicode_escape()?,
+                        _ => { return Err(self.error("invalid escape")); }
+                    };
+                    string.push(escaped);
+                }
+                Some((_, c)) => { string.push(c); }
+                None => { return Err(self.error("unterminated string")); }
+            }
+        }
+    }
+
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.chars.next().and_then(|(_, c)| c.to_digit(16)) {
+                Some(digit) => { code = code * 16 + digit; }
+                None => { return Err(self.error("invalid unicode escape")); }
+            }
+        }
+        Ok(code)
+    }
+
+
+    /// Parse the 'XXXX' of a '\uXXXX' escape. Characters outside the BMP
+    /// are escaped as a UTF-16 surrogate pair (e.g., '\uD83D\uDE00').
+    fn parse_unicode_escape(&mut self) -> Result<char, String> {
+        let mut code = self.parse_hex4()?;
+        if (0xD800..0xDC00).contains(&code) {
+            let low = match (self.chars.next(), self.chars.next()) {
+                (Some((_, '\\')), Some((_, 'u'))) => self.parse_hex4()?,
+                _ => { return Err(self.error("unpaired surrogate in unicode escape")); }
+            };
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("unpaired surrogate in unicode escape"));
+            }
+            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+        }
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => Err(self.error("invalid unicode escape")),
+        }
+    }
+
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let mut number = String::new();
+        while let Some((_, c)) = self.chars.peek() {
+            if c.is_ascii_digit() || "+-.eE".contains(*c) {
+                number.push(*c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        match number.parse::<f64>() {
+            Ok(number) => Ok(JsonValue::Number(number)),
+            Err(_) => Err(self.error("invalid number")),
+        }
+    }
+
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        let c = match self.chars.peek() {
+            Some((_, c)) => *c,
+            None => { return Err(self.error("unexpected end")); }
+        };
+        match c {
+            'n' => self.parse_literal("null", JsonValue::Null),
+            't' => self.parse_literal("true", JsonValue::Bool(true)),
+            'f' => self.parse_literal("false", JsonValue::Bool(false)),
+            '"' => Ok(JsonValue::String(self.parse_string()?)),
+            '[' => {
+                self.chars.next();
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if let Some((_, ']')) = self.chars.peek() {
+                    self.chars.next();
+                    return Ok(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(self.parse_value()?);
+                    self.skip_whitespace();
+                    match self.chars.next() {
+                        Some((_, ',')) => {}
+                        Some((_, ']')) => { return Ok(JsonValue::Array(items)); }
+                        _ => { return Err(self.error("expected ',' or ']'")); }
+                    }
+                }
+            }
+            '{' => {
+                self.chars.next();
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if let Some((_, '}')) = self.chars.peek() {
+                    self.chars.next();
+                    return Ok(JsonValue::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.parse_string()?;
+                    self.expect(':')?;
+                    members.push((key, self.parse_value()?));
+                    self.skip_whitespace();
+                    match self.chars.next() {
+                        Some((_, ',')) => {}
+                        Some((_, '}')) => { return Ok(JsonValue::Object(members)); }
+                        _ => { return Err(self.error("expected ',' or '}'")); }
+                    }
+                }
+            }
+            _ => self.parse_number(),
+        }
+    }
+}
+
+
+pub(crate) fn parse(text: &str) -> Result<JsonValue, String> {
+    let mut parser = Parser {
+        chars: text.char_indices().peekable(),
+        text,
+    };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.chars.peek().is_some() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_string(text: &str) -> Result<String, String> {
+        match parse(text)? {
+            JsonValue::String(string) => Ok(string),
+            _ => Err("not a string".to_string()),
+        }
+    }
+
+    #[test]
+    fn parse_values() {
+        let value = parse(" {\"a\": [1, -2.5e1, true, null], \"b\": {}} ").unwrap();
+        match value.get("a") {
+            Some(JsonValue::Array(items)) => {
+                assert_eq!(items.len(), 4);
+                assert!(matches!(items[1], JsonValue::Number(n) if n == -25.0));
+                assert!(matches!(items[2], JsonValue::Bool(true)));
+                assert!(matches!(items[3], JsonValue::Null));
+            }
+            _ => panic!("expected an array"),
+        }
+        assert!(matches!(value.get("b"), Some(JsonValue::Object(members)) if members.is_empty()));
+    }
+
+    #[test]
+    fn parse_escapes() {
+        assert_eq!(parse_string(r#""a\"b\\c\/d\n\t""#).unwrap(), "a\"b\\c/d\n\t");
+        assert_eq!(parse_string(r#""\u00e9\u4e2d""#).unwrap(), "é中");
+    }
+
+    #[test]
+    fn parse_surrogate_pairs() {
+        assert_eq!(parse_string(r#""\uD83D\uDE00""#).unwrap(), "😀");
+        assert_eq!(parse_string(r#""a\ud834\udd1eb""#).unwrap(), "a𝄞b");
+        assert!(parse_string(r#""\uD83D""#).is_err());
+        assert!(parse_string(r#""\uD83Dx""#).is_err());
+        assert!(parse_string(r#""\uD83D\u0041""#).is_err());
+        assert!(parse_string(r#""\uDE00""#).is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse("[1,\n 2,").err().unwrap(), "unexpected end at 2:4");
+        assert!(parse("[1 2]").err().unwrap().starts_with("expected ',' or ']'"));
+        assert!(parse("{\"a\" 1}").err().unwrap().starts_with("expected ':'"));
+        assert!(parse("\"abc").err().unwrap().starts_with("unterminated string"));
+        assert!(parse("nul").err().unwrap().starts_with("invalid literal"));
+        assert!(parse("[] x").err().unwrap().starts_with("unexpected trailing characters"));
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        let text = "/tmp/a \"b\"\\c\td\u{1}é😀";
+        assert_eq!(parse_string(&escape_string(text)).unwrap(), text);
+    }
+}
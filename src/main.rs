@@ -1,30 +1,98 @@
 mod data;
 mod app;
 mod config;
+mod database;
 mod colors;
 mod utils;
 mod strings;
+mod shell;
+mod score;
+mod import;
+mod export;
+mod json;
+mod matcher;
+mod regex;
+mod glob;
+mod exclude;
+mod picker;
+mod selection;
 
 
 use utils::write_dir;
 
 use std::env;
-use std::fs;
 use crate::app::get_home_dir;
 use crate::config::app_from_config;
 use crate::strings::HELP;
 
 
 
+// Options of 'z', handled below; they are also offered by 'cz complete'
+pub(crate) const OPTIONS: [&str; 25] = [
+    "-", "=", "-a", "-b", "-e", "-f", "-h", "-i", "-l", "-r", "-t", "-v",
+    "--clear", "--database-path", "--glob", "--help", "--id", "--ih",
+    "--in-repo", "--list-all", "--regex", "--remove-alias", "--repo", "--sync",
+    "--version",
+];
+
+// Commands of 'cz'; with 'jump', these words are substrings instead
+pub(crate) const COMMANDS: [&str; 9] = [
+    "add", "complete", "db", "export", "import", "init", "jump", "merge", "query",
+];
+
+
 fn main() {
     // Collect command-line arguments
-    let args: Vec<_> = env::args().collect();
+    let mut args: Vec<_> = env::args().collect();
+
+    // Query modes: the substrings are regexes or globs instead
+    let mut query_matcher = None;
+    for flag in ["--regex", "--glob"] {
+        if let Some(i) = args.iter().position(|arg| arg == flag) {
+            args.remove(i);
+            query_matcher = Some(flag.trim_start_matches('-'));
+        }
+    }
+    // Scopes: only roots of git repositories, or dirs in the current one
+    let mut scope = None;
+    for flag in ["--repo", "--in-repo"] {
+        if let Some(i) = args.iter().position(|arg| arg == flag) {
+            args.remove(i);
+            scope = Some(flag.trim_start_matches('-'));
+        }
+    }
+
+    // The shell functions run 'cz jump ARGS...', so that 'z add' or 'z init'
+    //   jump to a dir instead of running a command
+    let jump = args.len() > 1 && args[1] == "jump";
+    if jump {
+        args.remove(1);
+    }
+
+    // Print the shell integration; it neither needs the database nor writes
+    //   the output file
+    if !jump && args.len() > 1 && args[1] == "init" {
+        shell::init(&args);
+        return;
+    }
 
     // Initialize dirs and app
     let dirs = &mut Vec::new();
     let app = &mut app_from_config(dirs);
+    if let Some(matcher) = query_matcher {
+        app.matcher = matcher.to_string();
+    }
+    if let Some(scope) = scope {
+        app.scope = scope.to_string();
+    }
 
-    write_dir("".to_string());
+    // 'add' and 'complete' are run from the shell integration, and 'query'
+    //   from scripts, so they must not touch the output file
+    if jump || !(args.len() > 1 && (args[1] == "add" || args[1] == "complete" || args[1] == "query")) {
+        write_dir("".to_string());
+    }
+
+    let mut save_database = true;
 
     // If there is a dir argument, cd to the dir
     if args.len() > 1 {
@@ -37,6 +105,59 @@ fn main() {
         else if args[1] == "--clear" {
             app.clear_database();
         }
+        else if jump && COMMANDS.contains(&args[1].as_str()) {
+            app.do_cd(&args, "none");
+        }
+        else if args[1] == "add" {
+            // Record dirs without jumping to them
+            app.add_dirs(&args[2..]);
+        }
+        else if args[1] == "db" {
+            if args.len() > 2 && args[2] == "check" {
+                app.check_database();
+                // Checking must not touch the database
+                save_database = false;
+            }
+            else if args.len() > 2 && args[2] == "prune" {
+                let dry_run = args.len() > 3 && args[3] == "--dry-run";
+                app.prune_dirs(dry_run);
+                save_database = !dry_run;
+            }
+            else if args.len() > 2 && args[2] == "prune-excluded" {
+                let dry_run = args.len() > 3 && args[3] == "--dry-run";
+                app.prune_excluded(dry_run);
+                save_database = !dry_run;
+            }
+            else if args.len() > 2 && args[2] == "repair" {
+                app.repair_database();
+            }
+            else {
+                app.show_error(
+                    "Invalid db command",
+                    "allowed values: check, prune, prune-excluded, repair"
+                );
+            }
+        }
+        else if args[1] == "complete" {
+            app.complete(&args[2..]);
+            save_database = false;
+        }
+        else if args[1] == "query" {
+            app.query(&args[2..]);
+            save_database = false;
+        }
+        else if args[1] == "export" {
+            app.export(&args);
+            save_database = false;
+        }
+        else if args[1] == "import" {
+            app.import_dirs(&args);
+            save_database = !args.iter().any(|arg| arg == "--dry-run");
+        }
+        else if args[1] == "merge" {
+            app.merge_database(&args);
+            save_database = !args.iter().any(|arg| arg == "--dry-run");
+        }
         else if args[1] == "-" {
             app.go_to_previous();
         }
@@ -48,7 +169,17 @@ fn main() {
         }
         // Command option: list directories
         else if args[1] == "-l" {
-            app.list_existent();
+            let max_num = match args.get(2) {
+                Some(number) => match number.parse::<usize>() {
+                    Ok(number) => number,
+                    Err(_) => {
+                        app.show_error("Invalid number", number);
+                        0
+                    }
+                },
+                None => 0,
+            };
+            app.list_existent(max_num);
         }
         else if args[1] == "-i" {
             // Command option: interactive subdir selection
@@ -99,19 +230,7 @@ fn main() {
         app.interactive_cd(&args);
     }
     // Write dirs to database_dir_fn
-    let mut db_string = String::new();
-    for dir in app.dirs.iter() {
-        db_string.push_str(&format!("{}\n", dir.name));
-        db_string.push_str(&format!("{}\n", dir.counter));
-        db_string.push_str(&format!("{}\n", dir.last_access));
-        // Score must be a float in the format x.y
-        // db_string.push_str(&format!("{:.10}\n", dir.score));
-        if dir.alias.len() > 0 {
-            db_string.push_str(&format!("{}\n", dir.alias));
-        }
-        db_string.push_str("---\n");
+    if save_database {
+        database::save(&app.database_path, &app.loaded_dirs, app.dirs);
     }
-    fs::write(app.database_path.clone(), db_string).unwrap_or_else(
-        |e| panic!("Error writing file: {}", e)
-        );
 }
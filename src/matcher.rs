@@ -0,0 +1,357 @@
+use crate::glob;
+use crate::regex::Regex;
+
+
+
+pub(crate) const MATCHERS: [&str; 3] = ["substring", "fuzzy", "ordered"];
+// Query modes, set with '--regex' and '--glob'
+pub(crate) const QUERY_MATCHERS: [&str; 2] = ["regex", "glob"];
+pub(crate) const CASE_SENSITIVITIES: [&str; 3] = ["smart", "sensitive", "insensitive"];
+
+// Latin letters with diacritics (precomposed, NFC), and their base letters
+const LATIN_FOLDS: [(&str, &str); 43] = [
+    ("ÀÁÂÃÄÅĀĂĄ", "A"), ("àáâãäåāăą", "a"),
+    ("ÇĆĈĊČ", "C"), ("çćĉċč", "c"),
+    ("ĎĐ", "D"), ("ďđ", "d"),
+    ("ÈÉÊËĒĔĖĘĚ", "E"), ("èéêëēĕėęě", "e"),
+    ("ĜĞĠĢ", "G"), ("ĝğġģ", "g"),
+    ("ĤĦ", "H"), ("ĥħ", "h"),
+    ("ÌÍÎÏĨĪĬĮİ", "I"), ("ìíîïĩīĭįı", "i"),
+    ("Ĵ", "J"), ("ĵ", "j"),
+    ("Ķ", "K"), ("ķ", "k"),
+    ("ĹĻĽĿŁ", "L"), ("ĺļľŀł", "l"),
+    ("ÑŃŅŇ", "N"), ("ñńņň", "n"),
+    ("ÒÓÔÕÖØŌŎŐ", "O"), ("òóôõöøōŏő", "o"),
+    ("ŔŖŘ", "R"), ("ŕŗř", "r"),
+    ("ŚŜŞŠ", "S"), ("śŝşš", "s"),
+    ("ŢŤŦ", "T"), ("ţťŧ", "t"),
+    ("ÙÚÛÜŨŪŬŮŰŲ", "U"), ("ùúûüũūŭůűų", "u"),
+    ("Ŵ", "W"), ("ŵ", "w"),
+    ("ÝŶŸ", "Y"), ("ýÿŷ", "y"),
+    ("ŹŻŽ", "Z"), ("źżž", "z"),
+    ("Æ", "AE"), ("æ", "ae"),
+    ("Œ", "OE"), ("œ", "oe"),
+    ("ß", "ss"),
+];
+
+
+// Scores of the fuzzy matcher, per matched character
+const MATCH_SCORE: f64 = 1.0;
+const SEGMENT_START_BONUS: f64 = 2.0;
+const WORD_START_BONUS: f64 = 1.5;
+const CONSECUTIVE_BONUS: f64 = 1.5;
+const GAP_PENALTY: f64 = 0.05;
+const MAX_CHAR_SCORE: f64 = MATCH_SCORE + SEGMENT_START_BONUS + CONSECUTIVE_BONUS;
+
+
+/// How the dir names and the patterns are compared: whether the case is
+/// ignored, and whether the diacritics are removed.
+pub(crate) struct Folding {
+    ignore_case: bool,
+    strip_diacritics: bool,
+}
+
+
+impl Folding {
+    /// With 'smart' case sensitivity, the case is ignored unless some pattern
+    /// contains an uppercase letter.
+    pub(crate) fn new(case_sensitivity: &str, fold_unicode: bool, patterns: &[String]) -> Folding {
+        let ignore_case = match case_sensitivity {
+            "insensitive" => true,
+            "smart" => !patterns.iter().any(|pattern| pattern.chars().any(char::is_uppercase)),
+            _ => false,
+        };
+        Folding {
+            ignore_case,
+            strip_diacritics: fold_unicode,
+        }
+    }
+
+
+    pub(crate) fn fold(&self, text: &str) -> String {
+        let mut folded = String::with_capacity(text.len());
+        for c in text.chars() {
+            if self.strip_diacritics {
+                // Combining marks, as in decomposed (NFD) names
+                if ('\u{300}'..='\u{36f}').contains(&c) {
+                    continue;
+                }
+                if let Some((_, base)) = LATIN_FOLDS.iter().find(|(letters, _)| letters.contains(c)) {
+                    folded.push_str(base);
+                    continue;
+                }
+            }
+            folded.push(c);
+        }
+        if self.ignore_case {
+            folded.to_lowercase()
+        } else {
+            folded
+        }
+    }
+}
+
+
+/// Bonus for matching the character at position `i` of `name`: the start of
+/// a path segment is worth more than the start of a word, and both more than
+/// any other character.
+fn position_bonus(name: &[char], i: usize) -> f64 {
+    if i == 0 || name[i - 1] == '/' {
+        SEGMENT_START_BONUS
+    } else if "-_. ".contains(name[i - 1])
+        || (name[i - 1].is_lowercase() && name[i].is_uppercase())
+    {
+        WORD_START_BONUS
+    } else {
+        0.0
+    }
+}
+
+
+/// Quality, in (0, 1], of the best fuzzy match of `pattern` in `name`: all
+/// the characters of the pattern must appear in the name in the same order.
+/// Matches at segment and word starts, and contiguous ones, are preferred.
+fn fuzzy_quality(name: &[char], pattern: &[char]) -> Option<f64> {
+    if pattern.is_empty() {
+        return Some(1.0);
+    }
+    // best[j]: best score of the pattern so far, with its last character
+    //   matched at position j of the name
+    let mut best: Vec<Option<f64>> = name.iter().enumerate()
+        .map(|(j, c)| {
+            if *c == pattern[0] {
+                Some(MATCH_SCORE + position_bonus(name, j))
+            } else {
+                None
+            }
+        })
+        .collect();
+    for pattern_char in pattern.iter().skip(1) {
+        let mut next: Vec<Option<f64>> = vec![None; name.len()];
+        // Best previous score (with the gap penalty up to here) among the
+        //   positions that are not adjacent
+        let mut best_gapped: Option<f64> = None;
+        for j in 1..name.len() {
+            if j >= 2 {
+                if let Some(score) = best[j - 2] {
+                    best_gapped = Some(best_gapped.map_or(score, |best| best.max(score)));
+                }
+            }
+            best_gapped = best_gapped.map(|score| score - GAP_PENALTY);
+            if name[j] != *pattern_char {
+                continue;
+            }
+            let consecutive = best[j - 1].map(|score| score + CONSECUTIVE_BONUS);
+            let previous = match (consecutive, best_gapped) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                (a, b) => a.or(b),
+            };
+            next[j] = previous.map(|score| score + MATCH_SCORE + position_bonus(name, j));
+        }
+        best = next;
+    }
+    let score = best.iter().flatten().fold(f64::NEG_INFINITY, |a, b| a.max(*b));
+    if score == f64::NEG_INFINITY {
+        return None;
+    }
+    let quality = score / (MAX_CHAR_SCORE * pattern.len() as f64);
+    Some(quality.clamp(0.01, 1.0))
+}
+
+
+/// Check if the patterns appear in the name in the same order, without
+/// overlapping; the last `in_basename` patterns must appear in the final
+/// component of the path.
+fn ordered_match(name: &str, patterns: &[String], in_basename: usize) -> bool {
+    let basename_start = name.rfind('/').map_or(0, |i| i + 1);
+    let mut start = 0;
+    for (i, pattern) in patterns.iter().enumerate() {
+        if i + in_basename >= patterns.len() {
+            start = start.max(basename_start);
+        }
+        // The leftmost match leaves the most room for the next patterns
+        match name[start..].find(pattern.as_str()) {
+            Some(position) => { start += position + pattern.len(); }
+            None => { return false; }
+        }
+    }
+    true
+}
+
+
+/// Check if the dir name matches all the patterns, and return the quality
+/// of the match, in (0, 1], to weight the score of the dir.
+pub(crate) fn match_quality(matcher: &str, name: &str, patterns: &[String]) -> Option<f64> {
+    match matcher {
+        "fuzzy" => {
+            let name: Vec<char> = name.chars().collect();
+            let mut total_quality = 0.0;
+            for pattern in patterns.iter() {
+                let pattern: Vec<char> = pattern.chars().collect();
+                total_quality += fuzzy_quality(&name, &pattern)?;
+            }
+            Some(total_quality / patterns.len().max(1) as f64)
+        }
+        // Like zoxide: in order, and the last one in the final component
+        "ordered" => {
+            if ordered_match(name, patterns, 1) { Some(1.0) } else { None }
+        }
+        // In order, and all of them in the final component (used by '-b')
+        "basename" => {
+            if ordered_match(name, patterns, patterns.len()) { Some(1.0) } else { None }
+        }
+        // All the patterns are substrings of the name
+        _ => {
+            if patterns.iter().all(|pattern| name.contains(pattern.as_str())) {
+                Some(1.0)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+
+/// Compile the patterns of the 'regex' and 'glob' query modes.
+pub(crate) fn compile_patterns(matcher: &str, patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns.iter()
+        .map(|pattern| {
+            let regex = if matcher == "glob" { glob::compile(pattern) } else { Regex::new(pattern) };
+            regex.map_err(|error| format!("'{}': {}", pattern, error))
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn ordered_keeps_the_order() {
+        let src_app = patterns(&["src", "app"]);
+        assert_eq!(match_quality("ordered", "/src/web/app", &src_app), Some(1.0));
+        assert_eq!(match_quality("ordered", "/app/old/src", &src_app), None);
+        // The substring matcher takes them in any order
+        assert_eq!(match_quality("substring", "/app/old/src", &src_app), Some(1.0));
+    }
+
+    #[test]
+    fn ordered_last_keyword_in_final_component() {
+        let src_app = patterns(&["src", "app"]);
+        assert_eq!(match_quality("ordered", "/src/app/old", &src_app), None);
+        assert_eq!(match_quality("ordered", "/src/web/my-app", &src_app), Some(1.0));
+        // The other keywords can also be in the final component
+        assert_eq!(match_quality("ordered", "/home/src-app", &src_app), Some(1.0));
+        assert_eq!(match_quality("ordered", "/home/app-src", &src_app), None);
+    }
+
+    #[test]
+    fn ordered_keywords_do_not_overlap() {
+        assert_eq!(match_quality("ordered", "/tmp/a", &patterns(&["a", "a"])), None);
+        assert_eq!(match_quality("ordered", "/tmp/aa", &patterns(&["a", "a"])), Some(1.0));
+        assert_eq!(match_quality("ordered", "/a/b/a", &patterns(&["a", "a"])), Some(1.0));
+    }
+
+    #[test]
+    fn ordered_single_keyword() {
+        let app = patterns(&["app"]);
+        assert_eq!(match_quality("ordered", "/src/app", &app), Some(1.0));
+        assert_eq!(match_quality("ordered", "/app/src", &app), None);
+        assert_eq!(match_quality("ordered", "/any", &patterns(&[])), Some(1.0));
+    }
+
+    #[test]
+    fn basename_all_keywords_in_final_component() {
+        let src_app = patterns(&["src", "app"]);
+        assert_eq!(match_quality("basename", "/src/web/app", &src_app), None);
+        assert_eq!(match_quality("basename", "/home/src-app", &src_app), Some(1.0));
+        assert_eq!(match_quality("basename", "/home/app-src", &src_app), None);
+        assert_eq!(match_quality("basename", "/src/app", &patterns(&["app"])), Some(1.0));
+        assert_eq!(match_quality("basename", "/app/src", &patterns(&["app"])), None);
+    }
+
+    fn fuzzy(name: &str, pattern: &str) -> Option<f64> {
+        match_quality("fuzzy", name, &patterns(&[pattern]))
+    }
+
+    #[test]
+    fn fuzzy_matches_in_order() {
+        assert!(fuzzy("/home/user/projects/web", "prjwb").is_some());
+        assert!(fuzzy("/home/user/projects/web", "bwjrp").is_none());
+        assert!(fuzzy("/home/user/projects/web", "prjx").is_none());
+        assert_eq!(fuzzy("/any", ""), Some(1.0));
+    }
+
+    #[test]
+    fn fuzzy_quality_range() {
+        for name in ["/home/user/projects/web", "/p/r/o/j/w/e/b", "/srv/project-web"] {
+            let quality = fuzzy(name, "prjwb").unwrap();
+            assert!(quality > 0.0 && quality <= 1.0, "{}: {}", name, quality);
+        }
+        // Several patterns must all match
+        let web_src = patterns(&["web", "src"]);
+        assert!(match_quality("fuzzy", "/src/web", &web_src).is_some());
+        assert!(match_quality("fuzzy", "/srv/web", &web_src).is_none());
+    }
+
+    #[test]
+    fn fuzzy_prefers_segment_starts() {
+        let segment_start = fuzzy("/home/web", "web").unwrap();
+        let inside = fuzzy("/home/cobweb", "web").unwrap();
+        assert!(segment_start > inside, "{} <= {}", segment_start, inside);
+        let word_start = fuzzy("/home/my-web", "web").unwrap();
+        assert!(segment_start > word_start && word_start > inside);
+        assert!(fuzzy("/home/myWeb", "Web").unwrap() > fuzzy("/home/myXWeb", "Web").unwrap());
+    }
+
+    #[test]
+    fn fuzzy_prefers_contiguous_matches() {
+        let contiguous = fuzzy("/srv/abcdef", "bcd").unwrap();
+        let scattered = fuzzy("/srv/abxcxd", "bcd").unwrap();
+        assert!(contiguous > scattered, "{} <= {}", contiguous, scattered);
+        // The best alignment is chosen, not the leftmost one
+        let best = fuzzy("/srv/xbxcxd/abcdef", "bcd").unwrap();
+        assert_eq!(best, contiguous);
+    }
+
+    /// Check if the pattern matches the name as a substring, once folded.
+    fn folded_match(case_sensitivity: &str, fold_unicode: bool, name: &str, pattern: &str) -> bool {
+        let folding = Folding::new(case_sensitivity, fold_unicode, &patterns(&[pattern]));
+        folding.fold(name).contains(&folding.fold(pattern))
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(folded_match("smart", false, "/home/Documents", "doc"));
+        assert!(folded_match("smart", false, "/home/Documents", "Doc"));
+        assert!(!folded_match("smart", false, "/home/Documents", "DOC"));
+        assert!(!folded_match("smart", false, "/home/documents", "Doc"));
+    }
+
+    #[test]
+    fn sensitive_and_insensitive_case() {
+        assert!(!folded_match("sensitive", false, "/home/Documents", "doc"));
+        assert!(folded_match("sensitive", false, "/home/Documents", "Doc"));
+        assert!(folded_match("insensitive", false, "/home/Documents", "doc"));
+        assert!(folded_match("insensitive", false, "/home/Documents", "DOC"));
+    }
+
+    #[test]
+    fn diacritics() {
+        let nfc = "/home/R\u{e9}sum\u{e9}";
+        let nfd = "/home/Re\u{301}sume\u{301}";
+        for name in [nfc, nfd] {
+            assert!(folded_match("smart", true, name, "resume"));
+            assert!(folded_match("smart", true, name, "Résumé"));
+            assert!(!folded_match("smart", false, name, "resume"));
+            assert!(!folded_match("sensitive", true, name, "resume"));
+        }
+        assert!(folded_match("smart", false, nfc, "résumé"));
+        assert_eq!(Folding::new("sensitive", true, &[]).fold("Straße Œuvre"), "Strasse OEuvre");
+    }
+}
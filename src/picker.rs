@@ -0,0 +1,470 @@
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use crate::app::App;
+use crate::matcher::Folding;
+
+
+
+pub(crate) const PICKERS: [&str; 5] = ["builtin", "numbered", "fzf", "sk", "peco"];
+
+const DIM: &str = "\x1b[2m";
+const REVERSE: &str = "\x1b[7m";
+
+
+/// Entry of the picker; the ones that are not selectable (e.g., files in
+/// the interactive navigation) are only shown.
+pub(crate) struct PickerItem {
+    pub(crate) label: String,
+    pub(crate) preview: String,
+    pub(crate) selectable: bool,
+}
+
+
+pub(crate) enum Picked {
+    /// Indexes of the selected items
+    Selected(Vec<usize>),
+    Cancelled,
+    /// The terminal cannot be used (e.g., it is dumb), so the numbered
+    /// prompt has to be used instead
+    Unavailable,
+}
+
+
+enum Key {
+    Char(char),
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Tab,
+    Backspace,
+    ClearQuery,
+    DeleteWord,
+    Cancel,
+    Ignored,
+}
+
+
+fn stty(tty: &File, args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(tty.try_clone().ok()?))
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+
+/// The controlling terminal, in raw mode and on the alternate screen while
+/// it is open.
+struct Terminal {
+    tty: File,
+    saved_mode: String,
+    rows: usize,
+    columns: usize,
+}
+
+
+impl Terminal {
+    fn open() -> Option<Terminal> {
+        match env::var("TERM") {
+            Ok(term) if !term.is_empty() && term != "dumb" => {}
+            _ => { return None; }
+        }
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+        let saved_mode = stty(&tty, &["-g"])?;
+        let size = stty(&tty, &["size"])?;
+        let (rows, columns) = size.split_once(' ')?;
+        let mut rows: usize = rows.parse().ok()?;
+        let mut columns: usize = columns.parse().ok()?;
+        // Some terminals do not report their size
+        if rows == 0 || columns == 0 {
+            rows = 24;
+            columns = 80;
+        }
+        if rows < 4 || columns < 10 {
+            return None;
+        }
+        // Reads time out after 0.1 s, to tell a lone Esc from the escape
+        //   sequences of the arrow keys
+        stty(&tty, &["raw", "-echo", "min", "0", "time", "1"])?;
+        let mut terminal = Terminal { tty, saved_mode, rows, columns };
+        terminal.write("\x1b[?1049h");
+        Some(terminal)
+    }
+
+
+    fn write(&mut self, text: &str) {
+        let _ = self.tty.write_all(text.as_bytes());
+        let _ = self.tty.flush();
+    }
+
+
+    /// Read a byte; None if nothing is typed before the timeout.
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut buffer = [0u8];
+        match self.tty.read(&mut buffer) {
+            Ok(1) => Some(buffer[0]),
+            _ => None,
+        }
+    }
+
+
+    fn read_key(&mut self) -> Option<Key> {
+        let byte = self.read_byte()?;
+        let key = match byte {
+            b'\r' | b'\n' => Key::Enter,
+            b'\t' => Key::Tab,
+            0x7f | 0x08 => Key::Backspace,
+            // Ctrl-C, Ctrl-G
+            0x03 | 0x07 => Key::Cancel,
+            // Ctrl-N, Ctrl-P
+            0x0e => Key::Down,
+            0x10 => Key::Up,
+            // Ctrl-U, Ctrl-W
+            0x15 => Key::ClearQuery,
+            0x17 => Key::DeleteWord,
+            0x1b => match self.read_byte() {
+                None => Key::Cancel,
+                Some(b'[') | Some(b'O') => match self.read_byte() {
+                    Some(b'A') => Key::Up,
+                    Some(b'B') => Key::Down,
+                    Some(b'H') => Key::Home,
+                    Some(b'F') => Key::End,
+                    Some(b'5') => { self.read_byte(); Key::PageUp }
+                    Some(b'6') => { self.read_byte(); Key::PageDown }
+                    _ => Key::Ignored,
+                },
+                _ => Key::Ignored,
+            },
+            byte if byte >= 0x20 => {
+                // UTF-8 sequence
+                let length = if byte < 0x80 {
+                    1
+                } else if byte >= 0xf0 {
+                    4
+                } else if byte >= 0xe0 {
+                    3
+                } else {
+                    2
+                };
+                let mut bytes = vec![byte];
+                for _ in 1..length {
+                    bytes.push(self.read_byte().unwrap_or(0));
+                }
+                match String::from_utf8(bytes).ok().and_then(|text| text.chars().next()) {
+                    Some(c) => Key::Char(c),
+                    None => Key::Ignored,
+                }
+            }
+            _ => Key::Ignored,
+        };
+        Some(key)
+    }
+}
+
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        self.write("\x1b[?1049l");
+        let saved_mode = self.saved_mode.clone();
+        stty(&self.tty, &[saved_mode.as_str()]);
+    }
+}
+
+
+struct Picker<'a> {
+    app: &'a App<'a>,
+    header: &'a str,
+    items: &'a [PickerItem],
+    multi: bool,
+    query: String,
+    /// Indexes of the items that match the query
+    filtered: Vec<usize>,
+    /// Positions, in `filtered`, of the selectable items
+    selectable: Vec<usize>,
+    /// Position, in `selectable`, of the current item
+    cursor: usize,
+    /// First position of `filtered` on the screen
+    offset: usize,
+    marked: Vec<bool>,
+}
+
+
+impl<'a> Picker<'a> {
+    fn words(&self) -> Vec<String> {
+        self.query.split_whitespace().map(String::from).collect()
+    }
+
+
+    /// Keep the items that contain all the words of the query (smart case).
+    fn filter(&mut self) {
+        let words = self.words();
+        let folding = Folding::new("smart", false, &words);
+        let words: Vec<String> = words.iter().map(|word| folding.fold(word)).collect();
+        self.filtered = (0..self.items.len())
+            .filter(|i| {
+                let label = folding.fold(&self.items[*i].label);
+                words.iter().all(|word| label.contains(word.as_str()))
+            })
+            .collect();
+        self.selectable = (0..self.filtered.len())
+            .filter(|position| self.items[self.filtered[*position]].selectable)
+            .collect();
+        self.cursor = 0;
+        self.offset = 0;
+    }
+
+
+    fn move_cursor(&mut self, delta: isize) {
+        if self.selectable.is_empty() {
+            return;
+        }
+        let last = self.selectable.len() as isize - 1;
+        self.cursor = (self.cursor as isize + delta).clamp(0, last) as usize;
+    }
+
+
+    fn current_item(&self) -> Option<usize> {
+        self.selectable.get(self.cursor).map(|position| self.filtered[*position])
+    }
+
+
+    fn list_height(&self, terminal: &Terminal) -> usize {
+        // Query, status and preview lines
+        terminal.rows - 3
+    }
+
+
+    /// Label truncated to the width of the screen, with the characters
+    /// that match the query highlighted.
+    fn highlight(&self, label: &str, width: usize, style: &str) -> String {
+        let chars: Vec<char> = label.chars().take(width).collect();
+        let words = self.words();
+        let folding = Folding::new("smart", false, &words);
+        let folded: Vec<char> = folding.fold(label).chars().collect();
+        let mut highlighted = vec![false; chars.len()];
+        // Folding can change the number of characters; then, nothing is
+        //   highlighted
+        if folded.len() == label.chars().count() {
+            for word in words.iter() {
+                let word: Vec<char> = folding.fold(word).chars().collect();
+                if word.is_empty() || word.len() > folded.len() {
+                    continue;
+                }
+                for start in 0..=(folded.len() - word.len()) {
+                    if folded[start..start + word.len()] == word[..] {
+                        for flag in highlighted.iter_mut().skip(start).take(word.len()) {
+                            *flag = true;
+                        }
+                    }
+                }
+            }
+        }
+        let mut text = String::new();
+        for (c, highlight) in chars.iter().zip(highlighted.iter()) {
+            if *highlight {
+                text.push_str(&self.app.format("bold", "green", c.to_string()));
+                // Restore the style of the line
+                text.push_str(style);
+            } else {
+                text.push(*c);
+            }
+        }
+        text
+    }
+
+
+    fn render(&mut self, terminal: &mut Terminal) {
+        let height = self.list_height(terminal);
+        let width = terminal.columns;
+        let current_position = self.selectable.get(self.cursor).copied();
+        if let Some(position) = current_position {
+            if position < self.offset {
+                self.offset = position;
+            } else if position >= self.offset + height {
+                self.offset = position + 1 - height;
+            }
+        }
+
+        let mut lines: Vec<String> = Vec::new();
+        lines.push(format!("{} {}", self.app.format("bold", "blue", ">".to_string()), self.query));
+        let mut status = format!("  {}/{}", self.filtered.len(), self.items.len());
+        if self.multi {
+            status.push_str(&format!(" ({} marked)", self.marked.iter().filter(|m| **m).count()));
+        }
+        if !self.header.is_empty() {
+            status.push_str(&format!("  {}", self.header));
+        }
+        lines.push(self.app.format("bold", "", status.chars().take(width).collect()));
+        for position in self.offset..(self.offset + height).min(self.filtered.len()) {
+            let i = self.filtered[position];
+            let item = &self.items[i];
+            let pointer = if Some(position) == current_position { ">" } else { " " };
+            let mark = if self.marked[i] { "*" } else { " " };
+            let style = if !item.selectable {
+                DIM
+            } else if Some(position) == current_position {
+                REVERSE
+            } else {
+                ""
+            };
+            let label = self.highlight(&item.label, width.saturating_sub(3), style);
+            let line = if !item.selectable {
+                format!("   {}{}\x1b[0m", style, label)
+            } else if Some(position) == current_position {
+                format!("{}{} {}{}\x1b[0m", self.app.format("bold", "blue", pointer.to_string()), mark, style, label)
+            } else {
+                format!("{}{} {}", pointer, mark, label)
+            };
+            lines.push(line);
+        }
+        while lines.len() < height + 2 {
+            lines.push(String::new());
+        }
+        if let Some(i) = self.current_item() {
+            let preview: String = self.items[i].preview.chars().take(width).collect();
+            lines.push(self.app.format("bold", "blue", preview));
+        }
+
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        screen.push_str(&lines.join("\r\n"));
+        // Leave the cursor at the end of the query
+        screen.push_str(&format!("\x1b[1;{}H", self.query.chars().count() + 3));
+        terminal.write(&screen);
+    }
+
+
+    fn run(&mut self, terminal: &mut Terminal) -> Picked {
+        self.filter();
+        self.render(terminal);
+        loop {
+            let key = match terminal.read_key() {
+                Some(key) => key,
+                None => { continue; }
+            };
+            let page = self.list_height(terminal) as isize;
+            match key {
+                Key::Char(c) => {
+                    self.query.push(c);
+                    self.filter();
+                }
+                Key::Backspace => {
+                    self.query.pop();
+                    self.filter();
+                }
+                Key::ClearQuery => {
+                    self.query.clear();
+                    self.filter();
+                }
+                Key::DeleteWord => {
+                    let trimmed = self.query.trim_end().to_string();
+                    self.query = match trimmed.rfind(' ') {
+                        Some(i) => trimmed[..i + 1].to_string(),
+                        None => String::new(),
+                    };
+                    self.filter();
+                }
+                Key::Up => { self.move_cursor(-1); }
+                Key::Down => { self.move_cursor(1); }
+                Key::PageUp => { self.move_cursor(-page); }
+                Key::PageDown => { self.move_cursor(page); }
+                Key::Home => { self.move_cursor(-(self.selectable.len() as isize)); }
+                Key::End => { self.move_cursor(self.selectable.len() as isize); }
+                Key::Tab => {
+                    if self.multi {
+                        if let Some(i) = self.current_item() {
+                            self.marked[i] = !self.marked[i];
+                            self.move_cursor(1);
+                        }
+                    }
+                }
+                Key::Enter => {
+                    let marked: Vec<usize> = (0..self.items.len()).filter(|i| self.marked[*i]).collect();
+                    if !marked.is_empty() {
+                        return Picked::Selected(marked);
+                    }
+                    if let Some(i) = self.current_item() {
+                        return Picked::Selected(vec![i]);
+                    }
+                }
+                Key::Cancel => { return Picked::Cancelled; }
+                Key::Ignored => {}
+            }
+            self.render(terminal);
+        }
+    }
+}
+
+
+/// Let the user pick one item (or several, marked with Tab, if `multi`)
+/// in a full-screen list, filtered as the query is typed.
+pub(crate) fn pick(app: &App, header: &str, items: &[PickerItem], multi: bool) -> Picked {
+    let mut terminal = match Terminal::open() {
+        Some(terminal) => terminal,
+        None => { return Picked::Unavailable; }
+    };
+    let mut picker = Picker {
+        app,
+        header,
+        items,
+        multi,
+        query: String::new(),
+        filtered: Vec::new(),
+        selectable: Vec::new(),
+        cursor: 0,
+        offset: 0,
+        marked: vec![false; items.len()],
+    };
+    picker.run(&mut terminal)
+}
+
+
+/// Let the user pick one line (or several, if `multi`) with an external
+/// fuzzy finder ('fzf', 'sk' or 'peco'), which reads the lines from its
+/// standard input and prints the selected ones.
+pub(crate) fn pick_external(program: &str, lines: &[String], multi: bool) -> Picked {
+    let mut args: Vec<&str> = vec!["--prompt", "cz> "];
+    if program != "peco" {
+        // Keep the order of the lines, which are already ranked
+        args.extend(["--no-sort", "--delimiter", "\t"]);
+        if multi {
+            args.push("--multi");
+        }
+    }
+    let mut child = match Command::new(program)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        // E.g., the program is not installed
+        Err(_) => { return Picked::Unavailable; }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The program can exit before reading all the lines
+        let _ = stdin.write_all(format!("{}\n", lines.join("\n")).as_bytes());
+    }
+    let output = match child.wait_with_output() {
+        Ok(output) => output,
+        Err(_) => { return Picked::Unavailable; }
+    };
+    let selected: Vec<usize> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|selected_line| lines.iter().position(|line| line == selected_line))
+        .collect();
+    if !output.status.success() || selected.is_empty() {
+        return Picked::Cancelled;
+    }
+    Picked::Selected(selected)
+}
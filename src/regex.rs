@@ -0,0 +1,452 @@
+/// Minimal backtracking regular expressions (no external dependencies),
+/// enough to match directory names.
+///
+/// Supported syntax: literals, '.', '^', '$', '|', groups ('(...)' and
+/// '(?:...)'), classes ('[a-z]', '[^/]'), the escapes '\d', '\w', '\s' (and
+/// their negations '\D', '\W', '\S'), and the quantifiers '*', '+', '?',
+/// '{n}', '{n,}' and '{n,m}', also in their lazy form ('*?', ...).
+pub(crate) struct Regex {
+    alternatives: Vec<Vec<Node>>,
+}
+
+
+enum Node {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat(Repeat),
+}
+
+
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+
+struct Repeat {
+    node: Box<Node>,
+    min: usize,
+    max: Option<usize>,
+    greedy: bool,
+}
+
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let in_ranges = self.ranges.iter().any(|(first, last)| *first <= c && c <= *last);
+        in_ranges != self.negated
+    }
+}
+
+
+const DIGIT_RANGES: [(char, char); 1] = [('0', '9')];
+const WORD_RANGES: [(char, char); 4] = [('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+const SPACE_RANGES: [(char, char); 2] = [(' ', ' '), ('\t', '\r')];
+
+
+/// Ranges of the class escapes ('\d', '\w', '\s'), if `c` is one of them.
+fn escape_ranges(c: char) -> Option<&'static [(char, char)]> {
+    match c.to_ascii_lowercase() {
+        'd' => Some(&DIGIT_RANGES),
+        'w' => Some(&WORD_RANGES),
+        's' => Some(&SPACE_RANGES),
+        _ => None,
+    }
+}
+
+
+fn escaped_char(c: char) -> char {
+    match c {
+        't' => '\t',
+        'n' => '\n',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.position.min(self.chars.len()))
+    }
+
+
+    fn parse_alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.peek() == Some('|') {
+            self.next();
+            alternatives.push(self.parse_sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+
+    fn parse_sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+        Ok(nodes)
+    }
+
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some('(') => {
+                // Groups only group; nothing is captured
+                if self.peek() == Some('?') {
+                    self.next();
+                    if self.next() != Some(':') {
+                        return Err(self.error("unsupported group"));
+                    }
+                }
+                let alternatives = self.parse_alternatives()?;
+                if self.next() != Some(')') {
+                    return Err(self.error("missing ')'"));
+                }
+                Ok(Node::Group(alternatives))
+            }
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('[') => self.parse_class(),
+            Some('\\') => {
+                let c = match self.next() {
+                    Some(c) => c,
+                    None => { return Err(self.error("trailing '\\'")); }
+                };
+                match escape_ranges(c) {
+                    Some(ranges) => Ok(Node::Class(Class {
+                        ranges: ranges.to_vec(),
+                        negated: c.is_uppercase(),
+                    })),
+                    None => Ok(Node::Char(escaped_char(c))),
+                }
+            }
+            Some('*') | Some('+') | Some('?') => Err(self.error("nothing to repeat")),
+            Some(c) => Ok(Node::Char(c)),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let mut class = Class { ranges: Vec::new(), negated: false };
+        if self.peek() == Some('^') {
+            self.next();
+            class.negated = true;
+        }
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(']') if !first => { return Ok(Node::Class(class)); }
+                Some('\\') => match self.next() {
+                    Some(c) => {
+                        if let Some(ranges) = escape_ranges(c) {
+                            if c.is_uppercase() {
+                                return Err(self.error("unsupported negated escape in class"));
+                            }
+                            class.ranges.extend_from_slice(ranges);
+                            first = false;
+                            continue;
+                        }
+                        escaped_char(c)
+                    }
+                    None => { return Err(self.error("missing ']'")); }
+                },
+                Some(c) => c,
+                None => { return Err(self.error("missing ']'")); }
+            };
+            first = false;
+            // Range, unless the '-' is the last character of the class
+            if self.peek() == Some('-') && self.chars.get(self.position + 1).is_some_and(|c| *c != ']') {
+                self.next();
+                let last = match self.next() {
+                    Some('\\') => self.next().map(escaped_char),
+                    last => last,
+                };
+                match last {
+                    Some(last) if c <= last => { class.ranges.push((c, last)); }
+                    _ => { return Err(self.error("invalid range")); }
+                }
+            } else {
+                class.ranges.push((c, c));
+            }
+        }
+    }
+
+
+    /// Read a number of a '{n,m}' quantifier.
+    fn parse_number(&mut self) -> Option<usize> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.next();
+        }
+        self.chars[start..self.position].iter().collect::<String>().parse().ok()
+    }
+
+
+    fn parse_quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                // Otherwise, '{' is a literal
+                let start = self.position;
+                self.next();
+                let bounds = match self.parse_number() {
+                    Some(min) => match self.next() {
+                        Some('}') => Some((min, Some(min))),
+                        Some(',') => {
+                            let max = self.parse_number();
+                            match self.next() {
+                                Some('}') => Some((min, max)),
+                                _ => None,
+                            }
+                        }
+                        _ => None,
+                    },
+                    None => None,
+                };
+                match bounds {
+                    Some((min, Some(max))) if max < min => {
+                        return Err(self.error("invalid repetition"));
+                    }
+                    Some(bounds) => {
+                        // The closing '}' is already read
+                        self.position -= 1;
+                        bounds
+                    }
+                    None => {
+                        self.position = start;
+                        return Ok(atom);
+                    }
+                }
+            }
+            _ => { return Ok(atom); }
+        };
+        self.next();
+        let greedy = if self.peek() == Some('?') {
+            self.next();
+            false
+        } else {
+            true
+        };
+        Ok(Node::Repeat(Repeat { node: Box::new(atom), min, max, greedy }))
+    }
+}
+
+
+/// Match the nodes from `position`; `k` is called with the position where
+/// each match ends, until it accepts one.
+fn match_nodes(nodes: &[Node], text: &[char], position: usize, k: &mut dyn FnMut(usize) -> bool) -> bool {
+    match nodes.split_first() {
+        None => k(position),
+        Some((node, rest)) => {
+            match_node(node, text, position, &mut |next| match_nodes(rest, text, next, k))
+        }
+    }
+}
+
+
+fn match_node(node: &Node, text: &[char], position: usize, k: &mut dyn FnMut(usize) -> bool) -> bool {
+    match node {
+        Node::Char(c) => position < text.len() && text[position] == *c && k(position + 1),
+        Node::Any => position < text.len() && k(position + 1),
+        Node::Class(class) => {
+            position < text.len() && class.matches(text[position]) && k(position + 1)
+        }
+        Node::Start => position == 0 && k(position),
+        Node::End => position == text.len() && k(position),
+        Node::Group(alternatives) => {
+            for alternative in alternatives.iter() {
+                if match_nodes(alternative, text, position, k) {
+                    return true;
+                }
+            }
+            false
+        }
+        Node::Repeat(repeat) => match_repeat(repeat, text, position, 0, k),
+    }
+}
+
+
+fn match_repeat(
+    repeat: &Repeat,
+    text: &[char],
+    position: usize,
+    count: usize,
+    k: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    let can_stop = count >= repeat.min;
+    let can_continue = repeat.max.is_none_or(|max| count < max);
+    // Once the minimum is reached, empty matches would repeat forever
+    let once_more = |k: &mut dyn FnMut(usize) -> bool| {
+        can_continue && match_node(&repeat.node, text, position, &mut |next| {
+            (next != position || !can_stop) && match_repeat(repeat, text, next, count + 1, k)
+        })
+    };
+    // Greedy repetitions try to match once more first; lazy ones, to stop
+    if !repeat.greedy && can_stop && k(position) {
+        return true;
+    }
+    if once_more(k) {
+        return true;
+    }
+    repeat.greedy && can_stop && k(position)
+}
+
+
+impl Regex {
+    pub(crate) fn new(pattern: &str) -> Result<Regex, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            position: 0,
+        };
+        let alternatives = parser.parse_alternatives()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("unmatched ')'"));
+        }
+        Ok(Regex { alternatives })
+    }
+
+
+    /// Check if the regex matches anywhere in the text.
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        for start in 0..=text.len() {
+            for alternative in self.alternatives.iter() {
+                if match_nodes(alternative, &text, start, &mut |_| true) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    fn error(pattern: &str) -> String {
+        Regex::new(pattern).err().unwrap()
+    }
+
+    #[test]
+    fn literals_anchors_and_alternatives() {
+        assert!(is_match("web", "/src/web/app"));
+        assert!(!is_match("^web", "/src/web/app"));
+        assert!(is_match("app$", "/src/web/app"));
+        assert!(!is_match("web$", "/src/web/app"));
+        assert!(is_match("^/src/(web|api)/app$", "/src/api/app"));
+        assert!(is_match("a.c", "abc"));
+        assert!(is_match("a\\.c", "a.c"));
+        assert!(!is_match("a\\.c", "abc"));
+        assert!(is_match("", "anything"));
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert!(is_match("^[a-c]+$", "abcba"));
+        assert!(!is_match("^[a-c]+$", "abcd"));
+        assert!(is_match("^[^/]+$", "name"));
+        assert!(!is_match("^[^/]+$", "a/b"));
+        assert!(is_match("^[a-]$", "-"));
+        assert!(is_match("^[]]$", "]"));
+        assert!(is_match("^\\d+-\\w+\\s\\S$", "2024-notes_1 x"));
+        assert!(!is_match("\\D", "123"));
+        assert!(is_match("^[\\d_]+$", "1_2"));
+    }
+
+    #[test]
+    fn quantifiers() {
+        assert!(is_match("^ab*c$", "ac"));
+        assert!(is_match("^ab+c$", "abbc"));
+        assert!(!is_match("^ab+c$", "ac"));
+        assert!(is_match("^ab?c$", "abc"));
+        assert!(!is_match("^ab?c$", "abbc"));
+        assert!(is_match("^a{2}$", "aa"));
+        assert!(!is_match("^a{2}$", "aaa"));
+        assert!(is_match("^a{2,}$", "aaaa"));
+        assert!(!is_match("^a{2,}$", "a"));
+        assert!(is_match("^a{1,3}$", "aaa"));
+        assert!(!is_match("^a{1,3}$", "aaaa"));
+        assert!(is_match("^a{0,1}b$", "b"));
+        assert!(is_match("^(ab){2}$", "abab"));
+    }
+
+    #[test]
+    fn lazy_quantifiers() {
+        assert!(is_match("^a.*?b$", "axxbyyb"));
+        assert!(is_match("^a+?$", "aaa"));
+        assert!(is_match("^a{1,3}?b$", "aaab"));
+        assert!(is_match("^(a??)b$", "ab"));
+    }
+
+    #[test]
+    fn braces_without_bounds_are_literals() {
+        assert!(is_match("^a{,2}$", "a{,2}"));
+        assert!(!is_match("^a{,2}$", "aa"));
+        assert!(is_match("^a{x}$", "a{x}"));
+        assert!(is_match("^a{1$", "a{1"));
+        assert!(is_match("^a{1,2$", "a{1,2"));
+        assert!(is_match("^{$", "{"));
+    }
+
+    #[test]
+    fn empty_repeats_terminate() {
+        assert!(is_match("^(a*)*b$", "aaab"));
+        assert!(!is_match("^(a*)*b$", "aaaaaaaaaaaaaaaac"));
+        assert!(is_match("^(a?)*$", "aaa"));
+        assert!(is_match("^(a*)+$", ""));
+        assert!(is_match("^(|a)*b$", "aab"));
+        assert!(is_match("^(a*){2,3}b$", "b"));
+    }
+
+    #[test]
+    fn parser_errors() {
+        assert_eq!(error("(ab"), "missing ')' at position 3");
+        assert_eq!(error("ab)"), "unmatched ')' at position 2");
+        assert_eq!(error("[ab"), "missing ']' at position 3");
+        assert_eq!(error("*a"), "nothing to repeat at position 1");
+        assert_eq!(error("a|+"), "nothing to repeat at position 3");
+        assert_eq!(error("ab\\"), "trailing '\\' at position 3");
+        assert_eq!(error("(?=a)"), "unsupported group at position 3");
+        assert_eq!(error("[z-a]"), "invalid range at position 4");
+        assert_eq!(error("a{3,1}"), "invalid repetition at position 6");
+        assert_eq!(error("[\\D]"), "unsupported negated escape in class at position 3");
+    }
+}
@@ -0,0 +1,133 @@
+pub(crate) const SCORE_MODELS: [&str; 4] = ["frecency", "buckets", "count", "recency"];
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = HOUR * 24;
+const WEEK: i64 = DAY * 7;
+
+
+/// Parameters of the score models, set from the configuration.
+pub(crate) struct ScoreModel {
+    pub(crate) model: String,
+    /// Decay rate per second of the 'frecency' model
+    pub(crate) decay: f64,
+    /// Multipliers of the 'buckets' model for dirs accessed within the last
+    /// hour, day, week, and before
+    pub(crate) buckets: [f64; 4],
+}
+
+
+impl ScoreModel {
+    pub(crate) fn new(model: &str, decay: f64, buckets: &str) -> ScoreModel {
+        if !SCORE_MODELS.contains(&model) {
+            panic!(
+                "Invalid score_model '{}'; allowed values: {}",
+                model, SCORE_MODELS.join(", ")
+            );
+        }
+        let buckets = buckets.split(',')
+            .map(|bucket| bucket.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>();
+        let buckets = match buckets {
+            Ok(buckets) if buckets.len() == 4 => [buckets[0], buckets[1], buckets[2], buckets[3]],
+            _ => { panic!("Invalid score_buckets; four comma-separated numbers are expected"); }
+        };
+        ScoreModel {
+            model: model.to_string(),
+            decay,
+            buckets,
+        }
+    }
+
+
+    pub(crate) fn score(&self, counter: i64, last_access: i64, current_seconds: i64) -> f64 {
+        let age = (current_seconds - last_access).max(0);
+        match self.model.as_str() {
+            // Frequency and recency with a continuous decay (z's 'frecent')
+            "frecency" => {
+                10000.0 * counter as f64 * (3.75 / ((self.decay * age as f64 + 1.0) + 0.25))
+            }
+            // Frequency weighted by how recent the last access is (classic z
+            //   and zoxide)
+            "buckets" => {
+                let bucket = if age < HOUR {
+                    self.buckets[0]
+                } else if age < DAY {
+                    self.buckets[1]
+                } else if age < WEEK {
+                    self.buckets[2]
+                } else {
+                    self.buckets[3]
+                };
+                counter as f64 * bucket
+            }
+            "count" => { counter as f64 }
+            // Most recently accessed first
+            _ => { last_access as f64 }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn frecency_matches_the_original_formula() {
+        let model = ScoreModel::new("frecency", 0.0001, "4,2,0.5,0.25");
+        for (counter, age) in [(1, 0), (3, 60), (10, HOUR), (7, DAY), (42, 30 * DAY)] {
+            let last_access = NOW - age;
+            let original = 10000.0 * counter as f64
+                * (3.75 / ((0.0001 * (NOW - last_access) as f64 + 1.0) + 0.25));
+            assert_eq!(model.score(counter, last_access, NOW), original);
+        }
+    }
+
+    #[test]
+    fn frecency_decays_with_age() {
+        let model = ScoreModel::new("frecency", 0.0001, "4,2,0.5,0.25");
+        assert!(model.score(5, NOW, NOW) > model.score(5, NOW - DAY, NOW));
+        assert!(model.score(6, NOW, NOW) > model.score(5, NOW, NOW));
+    }
+
+    #[test]
+    fn buckets_boundaries() {
+        let model = ScoreModel::new("buckets", 0.0001, "4,2,0.5,0.25");
+        assert_eq!(model.score(2, NOW, NOW), 8.0);
+        assert_eq!(model.score(2, NOW - HOUR + 1, NOW), 8.0);
+        assert_eq!(model.score(2, NOW - HOUR, NOW), 4.0);
+        assert_eq!(model.score(2, NOW - DAY + 1, NOW), 4.0);
+        assert_eq!(model.score(2, NOW - DAY, NOW), 1.0);
+        assert_eq!(model.score(2, NOW - WEEK + 1, NOW), 1.0);
+        assert_eq!(model.score(2, NOW - WEEK, NOW), 0.5);
+        assert_eq!(model.score(2, NOW - 365 * DAY, NOW), 0.5);
+        // Accesses in the future (e.g., clock changes) are recent
+        assert_eq!(model.score(2, NOW + DAY, NOW), 8.0);
+    }
+
+    #[test]
+    fn count_ranks_by_visits() {
+        let model = ScoreModel::new("count", 0.0001, "4,2,0.5,0.25");
+        let old_frequent = model.score(10, NOW - 365 * DAY, NOW);
+        let new_rare = model.score(2, NOW, NOW);
+        assert!(old_frequent > new_rare);
+        assert_eq!(model.score(3, NOW - DAY, NOW), model.score(3, NOW, NOW));
+    }
+
+    #[test]
+    fn recency_ranks_by_last_access() {
+        let model = ScoreModel::new("recency", 0.0001, "4,2,0.5,0.25");
+        let old_frequent = model.score(100, NOW - DAY, NOW);
+        let new_rare = model.score(1, NOW - 60, NOW);
+        assert!(new_rare > old_frequent);
+        assert_eq!(model.score(1, NOW - 60, NOW), model.score(50, NOW - 60, NOW));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid score_buckets")]
+    fn buckets_need_four_numbers() {
+        ScoreModel::new("buckets", 0.0001, "4,2,1");
+    }
+}
@@ -0,0 +1,141 @@
+/// Parse a selection of the numbers from 1 to `max`, as introduced in the
+/// numbered lists: numbers ('8'), ranges ('1-5', and '10-' up to `max`),
+/// 'all', and negations ('^3', '^2-4'), which leave numbers out of the
+/// selection (out of all of them, if there are only negations).
+///
+/// The numbers are returned in ascending order. If some tokens are not
+/// valid, nothing is returned but the errors of all of them.
+pub(crate) fn parse_selection(selection: &str, max: usize) -> Result<Vec<usize>, Vec<String>> {
+    let mut selected = vec![false; max + 1];
+    let mut negated = vec![false; max + 1];
+    let mut has_numbers = false;
+    let mut has_negations = false;
+    let mut errors: Vec<String> = Vec::new();
+
+    let tokens = selection.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty());
+    for token in tokens {
+        let (numbers, is_negation) = match token.strip_prefix('^') {
+            Some(rest) => (rest, true),
+            None => (token, false),
+        };
+        match parse_range(numbers, max) {
+            Ok((first, last)) => {
+                let marks = if is_negation { &mut negated } else { &mut selected };
+                for mark in marks[first..=last].iter_mut() {
+                    *mark = true;
+                }
+                has_numbers = has_numbers || !is_negation;
+                has_negations = has_negations || is_negation;
+            }
+            Err(error) => { errors.push(format!("'{}' ({})", token, error)); }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let from_all = has_negations && !has_numbers;
+    let numbers: Vec<usize> = (1..=max)
+        .filter(|i| (selected[*i] || from_all) && !negated[*i])
+        .collect();
+    if numbers.is_empty() {
+        return Err(vec!["nothing selected".to_string()]);
+    }
+    Ok(numbers)
+}
+
+
+/// Parse 'N', 'N-M', 'N-' or 'all' into the first and the last numbers.
+fn parse_range(range: &str, max: usize) -> Result<(usize, usize), String> {
+    if range == "all" {
+        return if max > 0 { Ok((1, max)) } else { Err("empty list".to_string()) };
+    }
+    let (first, last) = match range.split_once('-') {
+        Some((first, "")) => (parse_number(first, max)?, max),
+        Some((first, last)) => (parse_number(first, max)?, parse_number(last, max)?),
+        None => {
+            let number = parse_number(range, max)?;
+            (number, number)
+        }
+    };
+    if first > last {
+        return Err("invalid range".to_string());
+    }
+    Ok((first, last))
+}
+
+
+fn parse_number(number: &str, max: usize) -> Result<usize, String> {
+    match number.parse::<usize>() {
+        Ok(number) if (1..=max).contains(&number) => Ok(number),
+        Ok(number) => Err(format!("{} is not between 1 and {}", number, max)),
+        Err(_) => Err("not a number".to_string()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_and_ranges() {
+        assert_eq!(parse_selection("1-5 8 10-", 12), Ok(vec![1, 2, 3, 4, 5, 8, 10, 11, 12]));
+        assert_eq!(parse_selection("3,1, 3", 5), Ok(vec![1, 3]));
+        assert_eq!(parse_selection("2-2", 5), Ok(vec![2]));
+        assert_eq!(parse_selection("all", 3), Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn negations() {
+        assert_eq!(parse_selection("all ^3", 5), Ok(vec![1, 2, 4, 5]));
+        assert_eq!(parse_selection("1-5 ^2-4", 8), Ok(vec![1, 5]));
+        // Only negations: out of all the numbers
+        assert_eq!(parse_selection("^1 ^4-", 5), Ok(vec![2, 3]));
+        assert_eq!(parse_selection("^all", 3), Err(vec!["nothing selected".to_string()]));
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert_eq!(
+            parse_selection("9", 8),
+            Err(vec!["'9' (9 is not between 1 and 8)".to_string()]),
+        );
+        assert_eq!(
+            parse_selection("^0", 8),
+            Err(vec!["'^0' (0 is not between 1 and 8)".to_string()]),
+        );
+        assert_eq!(
+            parse_selection("7-9", 8),
+            Err(vec!["'7-9' (9 is not between 1 and 8)".to_string()]),
+        );
+        assert_eq!(parse_selection("all", 0), Err(vec!["'all' (empty list)".to_string()]));
+    }
+
+    #[test]
+    fn reversed_ranges() {
+        assert_eq!(parse_selection("5-3", 8), Err(vec!["'5-3' (invalid range)".to_string()]));
+        assert_eq!(parse_selection("^4-2", 8), Err(vec!["'^4-2' (invalid range)".to_string()]));
+    }
+
+    #[test]
+    fn all_bad_tokens_are_reported() {
+        assert_eq!(
+            parse_selection("x 2 9 5-3 -1", 8),
+            Err(vec![
+                "'x' (not a number)".to_string(),
+                "'9' (9 is not between 1 and 8)".to_string(),
+                "'5-3' (invalid range)".to_string(),
+                "'-1' (not a number)".to_string(),
+            ]),
+        );
+    }
+
+    #[test]
+    fn nothing_selected() {
+        assert_eq!(parse_selection("", 5), Err(vec!["nothing selected".to_string()]));
+        assert_eq!(parse_selection(" , ", 5), Err(vec!["nothing selected".to_string()]));
+        assert_eq!(parse_selection("2 ^2", 5), Err(vec!["nothing selected".to_string()]));
+    }
+}
@@ -0,0 +1,346 @@
+use std::process::exit;
+
+
+
+pub(crate) const SHELLS: [&str; 4] = ["bash", "zsh", "fish", "posix"];
+pub(crate) const HOOKS: [&str; 3] = ["none", "prompt", "pwd"];
+
+
+/* Templates of the shell integration. '{cmd}' is replaced by the name of the
+ * function. In 'pwd' mode, the hook only records the dir if it is not the
+ * last one recorded, and the function sets it before 'cd', so jumps done
+ * with the function (already recorded by 'cz') are not counted twice.
+ */
+
+const BASH_FUNCTION: &str = r#"
+{cmd}() {
+    local cz_out zout
+    cz_out=$(mktemp "${TMPDIR:-/tmp}/cz.XXXXXX") || return 1
+    CZ_OUTPUT="$cz_out" cz jump "$@"
+    zout=$(cat "$cz_out")
+    rm -f "$cz_out"
+    if [[ -n "$zout" ]]; then
+        __cz_oldpwd="$zout"
+        cd "$zout"
+    fi
+}
+"#;
+
+// Completion asks 'cz complete' for the candidates of the words typed so
+//   far, the last one being the word under completion
+const BASH_COMPLETION: &str = r#"
+__cz_complete_{cmd}() {
+    local IFS=$'\n'
+    COMPREPLY=($(cz complete "${COMP_WORDS[@]:1:COMP_CWORD}"))
+}
+complete -o filenames -F __cz_complete_{cmd} {cmd}
+"#;
+
+const ZSH_COMPLETION: &str = r#"
+if (( $+functions[compdef] )); then
+    __cz_complete_{cmd}() {
+        local -a candidates
+        candidates=(${(f)"$(cz complete "${(@)words[2,CURRENT]}")"})
+        (( ${#candidates} )) && compadd -U -- "${candidates[@]}"
+    }
+    compdef __cz_complete_{cmd} {cmd}
+fi
+"#;
+
+const FISH_COMPLETION: &str = r#"
+complete -c {cmd} -f -a '(cz complete (commandline -opc)[2..-1] (commandline -ct))'
+"#;
+
+const BASH_HOOK_PROMPT: &str = r#"
+__cz_hook() {
+    cz add "$PWD"
+}
+"#;
+
+const BASH_HOOK_PWD: &str = r#"
+__cz_hook() {
+    if [[ "$__cz_oldpwd" != "$PWD" ]]; then
+        __cz_oldpwd="$PWD"
+        cz add "$PWD"
+    fi
+}
+"#;
+
+const BASH_HOOK_INSTALL: &str = r#"
+if [[ ";${PROMPT_COMMAND:-};" != *";__cz_hook;"* ]]; then
+    PROMPT_COMMAND="__cz_hook${PROMPT_COMMAND:+;${PROMPT_COMMAND}}"
+fi
+"#;
+
+const ZSH_HOOK_INSTALL_PROMPT: &str = r#"
+if [[ ${precmd_functions[(Ie)__cz_hook]:-0} -eq 0 ]]; then
+    precmd_functions+=(__cz_hook)
+fi
+"#;
+
+const ZSH_HOOK_INSTALL_PWD: &str = r#"
+if [[ ${chpwd_functions[(Ie)__cz_hook]:-0} -eq 0 ]]; then
+    chpwd_functions+=(__cz_hook)
+fi
+"#;
+
+const FISH_FUNCTION: &str = r#"
+function {cmd}
+    set -l cz_tmpdir /tmp
+    set -q TMPDIR[1]; and set cz_tmpdir $TMPDIR
+    set -l cz_out (mktemp "$cz_tmpdir/cz.XXXXXX")
+    or return 1
+    env CZ_OUTPUT=$cz_out cz jump $argv
+    set -l zout (cat $cz_out)
+    rm -f $cz_out
+    if test -n "$zout"
+        set -g __cz_oldpwd $zout
+        cd $zout
+    end
+end
+"#;
+
+const FISH_HOOK_PROMPT: &str = r#"
+function __cz_hook --on-event fish_prompt
+    cz add $PWD
+end
+"#;
+
+const FISH_HOOK_PWD: &str = r#"
+function __cz_hook --on-variable PWD
+    if test "$__cz_oldpwd" != "$PWD"
+        set -g __cz_oldpwd $PWD
+        cz add $PWD
+    end
+end
+"#;
+
+const POSIX_FUNCTION: &str = r#"
+{cmd}() {
+    __cz_out=$(mktemp "${TMPDIR:-/tmp}/cz.XXXXXX") || return 1
+    CZ_OUTPUT="$__cz_out" cz jump "$@"
+    __cz_zout=$(cat "$__cz_out")
+    rm -f "$__cz_out"
+    if [ -n "$__cz_zout" ]; then
+        cd "$__cz_zout"
+    fi
+    unset __cz_out __cz_zout
+}
+"#;
+
+// PS1 is expanded in a subshell, so the hook cannot remember the last
+//   recorded dir: only the 'prompt' hook is available
+const POSIX_HOOK_PROMPT: &str = r#"
+__cz_hook() {
+    cz add "$PWD"
+}
+
+case "$PS1" in
+    *__cz_hook*) ;;
+    *) PS1="${PS1}\$(__cz_hook)" ;;
+esac
+"#;
+
+
+fn build_script(shell: &str, cmd: &str, hook: &str) -> Result<String, String> {
+    let mut script = String::from("# Generated by 'cz init'\n");
+    match shell {
+        "bash" | "zsh" => {
+            script.push_str(BASH_FUNCTION);
+            if shell == "bash" {
+                script.push_str(BASH_COMPLETION);
+            } else {
+                script.push_str(ZSH_COMPLETION);
+            }
+            match hook {
+                "prompt" => { script.push_str(BASH_HOOK_PROMPT); }
+                "pwd" => { script.push_str(BASH_HOOK_PWD); }
+                _ => {}
+            }
+            if hook != "none" {
+                if shell == "bash" {
+                    script.push_str(BASH_HOOK_INSTALL);
+                } else if hook == "prompt" {
+                    script.push_str(ZSH_HOOK_INSTALL_PROMPT);
+                } else {
+                    script.push_str(ZSH_HOOK_INSTALL_PWD);
+                }
+            }
+        }
+        "fish" => {
+            script.push_str(FISH_FUNCTION);
+            script.push_str(FISH_COMPLETION);
+            match hook {
+                "prompt" => { script.push_str(FISH_HOOK_PROMPT); }
+                "pwd" => { script.push_str(FISH_HOOK_PWD); }
+                _ => {}
+            }
+        }
+        "posix" => {
+            script.push_str(POSIX_FUNCTION);
+            match hook {
+                "prompt" => { script.push_str(POSIX_HOOK_PROMPT); }
+                "pwd" => {
+                    return Err(
+                        "The 'pwd' hook is not supported by posix shells; use 'prompt'".to_string()
+                    );
+                }
+                _ => {}
+            }
+        }
+        _ => {
+            return Err(format!(
+                "Unsupported shell '{}'; allowed values: {}",
+                shell, SHELLS.join(", ")
+            ));
+        }
+    }
+    Ok(script.replace("{cmd}", cmd))
+}
+
+
+/// The name is pasted into the script, so it must be a plain identifier,
+/// valid as a function name in every shell (e.g., posix shells do not
+/// allow '-').
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+
+fn exit_with_error(text: &str) -> ! {
+    // The output is meant to be evaluated by the shell, so report errors to
+    //   stderr
+    eprintln!("{}", text);
+    exit(1);
+}
+
+
+/// Print the shell integration requested by `cz init SHELL [--cmd NAME]
+/// [--hook HOOK]`.
+pub(crate) fn init(args: &[String]) {
+    let mut shell: Option<&str> = None;
+    let mut cmd = "z";
+    let mut hook: Option<&str> = None;
+
+    let mut i = 2;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        if arg == "--cmd" || arg == "--hook" {
+            if i + 1 >= args.len() {
+                exit_with_error(&format!("No value provided for '{}'", arg));
+            }
+            let value = args[i + 1].as_str();
+            if arg == "--cmd" {
+                if !is_valid_name(value) {
+                    exit_with_error(&format!(
+                        "Invalid function name '{}'; letters, digits and '_' expected",
+                        value
+                    ));
+                }
+                cmd = value;
+            } else {
+                if !HOOKS.contains(&value) {
+                    exit_with_error(&format!(
+                        "Invalid hook '{}'; allowed values: {}",
+                        value, HOOKS.join(", ")
+                    ));
+                }
+                hook = Some(value);
+            }
+            i += 2;
+        } else if shell.is_none() {
+            shell = Some(arg);
+            i += 1;
+        } else {
+            exit_with_error(&format!("Unexpected argument '{}'", arg));
+        }
+    }
+
+    let shell = match shell {
+        Some(shell) => shell,
+        None => {
+            exit_with_error(&format!(
+                "No shell provided; allowed values: {}",
+                SHELLS.join(", ")
+            ));
+        }
+    };
+    // Posix shells only support the 'prompt' hook
+    let hook = match hook {
+        Some(hook) => hook,
+        None if shell == "posix" => "prompt",
+        None => "pwd",
+    };
+    match build_script(shell, cmd, hook) {
+        Ok(script) => { print!("{}", script); }
+        Err(error) => { exit_with_error(&error); }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bash_script() {
+        let script = build_script("bash", "j", "pwd").unwrap();
+        assert!(script.contains("\nj() {"));
+        assert!(script.contains("cz jump \"$@\""));
+        assert!(script.contains("complete -o filenames -F __cz_complete_j j"));
+        assert!(script.contains("__cz_hook() {"));
+        assert!(script.contains("PROMPT_COMMAND=\"__cz_hook"));
+        assert!(!script.contains("{cmd}"));
+        let script = build_script("bash", "j", "none").unwrap();
+        assert!(!script.contains("__cz_hook"));
+    }
+
+    #[test]
+    fn zsh_script() {
+        let script = build_script("zsh", "j", "pwd").unwrap();
+        assert!(script.contains("\nj() {"));
+        assert!(script.contains("compdef __cz_complete_j j"));
+        assert!(script.contains("chpwd_functions+=(__cz_hook)"));
+        let script = build_script("zsh", "j", "prompt").unwrap();
+        assert!(script.contains("precmd_functions+=(__cz_hook)"));
+        assert!(!script.contains("{cmd}"));
+    }
+
+    #[test]
+    fn fish_script() {
+        let script = build_script("fish", "j", "pwd").unwrap();
+        assert!(script.contains("function j\n"));
+        assert!(script.contains("cz jump $argv"));
+        assert!(script.contains("complete -c j -f"));
+        assert!(script.contains("function __cz_hook --on-variable PWD"));
+        let script = build_script("fish", "j", "prompt").unwrap();
+        assert!(script.contains("function __cz_hook --on-event fish_prompt"));
+        assert!(!script.contains("{cmd}"));
+    }
+
+    #[test]
+    fn posix_script() {
+        let script = build_script("posix", "j", "prompt").unwrap();
+        assert!(script.contains("\nj() {"));
+        assert!(script.contains("PS1=\"${PS1}\\$(__cz_hook)\""));
+        assert!(!script.contains("{cmd}"));
+        assert!(build_script("posix", "j", "pwd").is_err());
+        assert!(build_script("csh", "j", "none").is_err());
+    }
+
+    #[test]
+    fn function_names() {
+        assert!(is_valid_name("z"));
+        assert!(is_valid_name("_cd2"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("a-b"));
+        assert!(!is_valid_name("2z"));
+        assert!(!is_valid_name("z;ls"));
+    }
+}
@@ -1,4 +1,7 @@
-pub(crate) const DEFAULT_CONFIG: &str = "\
+// The default config; it is also shown at the end of the help
+macro_rules! default_config {
+    () => {
+"\
 theme = dark
 max_results = 9
 abs_paths = true
@@ -7,11 +10,34 @@ database_path = $HOME/.local/share/contemporary-z/directories.dir
 substring = score
 show_files = none
 nav_start_number = 1
-";
+max_age = 60
+max_entries = 0
+max_total_count = 0
+keep_aliased = true
+score_model = frecency
+score_decay = 0.0001
+score_buckets = 4,2,0.5,0.25
+matcher = substring
+case_sensitivity = smart
+fold_unicode = false
+exclude =
+fallback = true
+fallback_dirs = 10
+proximity_boost = 0
+proximity_git_root = false
+current_dir = demote
+picker = numbered
+"
+    };
+}
 
 
-pub(crate) const HELP: &str = "\
+pub(crate) const DEFAULT_CONFIG: &str = default_config!();
+
+
+pub(crate) const HELP: &str = concat!("\
 Usage: z [OPTION]... [DIRECTORY|SUBSTRING]...
+  or:  cz COMMAND [ARG]...
 
 FUNCTIONALITY:
   * If no option nor directory or substrings are specified, 'cz' prints a
@@ -39,6 +65,14 @@ Mandatory arguments to long options are mandatory for short options too.
   --database-path            show the path of the directories database.
   -e SUBSTRING               force substring matching by score.
   -f SUBSTRING               force substring match list for SUBSTRING
+      --glob                 match the substrings as globs (e.g.,
+                               'z --glob \'~/work/*/api\''), like '--regex':
+                               '*' matches within a path component, '**'
+                               across components.
+      --in-repo              only consider the directories in the git
+                               repository of the current directory (e.g.,
+                               'z --in-repo api'); it can be combined with the
+                               other options, like '-f' or '-l'.
   -i                         interactive selection (using a numbered list) of
                                the subdirectories of the current directory.
       --ih                   interactive selection, but including hidden
@@ -49,14 +83,109 @@ Mandatory arguments to long options are mandatory for short options too.
                                if no NUMBER is provided, the max_results number
                                from configuration is used.
       --list-all             list all the directories of the database
-  -r                         remove a directory from the database, interactively.
+  -r                         remove directories from the database,
+                               interactively; they are selected by numbers,
+                               ranges ('1-5', '10-') and 'all', and '^' leaves
+                               some out (e.g., '1-5 8 10-' or 'all ^3').
+      --regex                match the substrings as regular expressions (e.g.,
+                               'z --regex \'^~/work/.*-api$\''), against the
+                               paths both as stored and as listed (with '~'
+                               and '>'); it can be combined with '-f' and '-r'.
       --remove-alias         remove an alias, interactively.
+      --repo                 only consider the roots of git repositories
+                               (directories with a '.git'), like '--in-repo'.
   -t SUBSTRING               force substring matching by shortest path.
       --sync                 sync directories (remove all non-existent
                                 directories).
       --help     display this help and exit.
   -v, --version              display version information and exit.
 
+COMMANDS:
+  add DIRECTORY...           record the directories in the database without
+                               jumping to them (used by the shell hooks).
+  complete [WORD]...         print the completion candidates of the words
+                               typed after 'z', the last one being the word
+                               under completion: options, aliases and
+                               matching directories (used by the completion
+                               scripts of 'cz init').
+  db check                   check the directories database and report the
+                               records that cannot be read.
+  db prune [--dry-run]       remove the directories that do not meet the
+                               retention options ('max_age', 'max_entries',
+                               'max_total_count' and 'keep_aliased'), and
+                               report them; with '--dry-run', only report
+                               them.
+  db prune-excluded [--dry-run]
+                             remove the directories that match the 'exclude'
+                               patterns (e.g., after adding new ones), except
+                               the aliased ones, and report them; with
+                               '--dry-run', only report them.
+  db repair                  move the records that cannot be read to a
+                               '.corrupt' file next to the database (this is
+                               also done whenever the database is written).
+  export [--format FORMAT]   print all the directories of the database, with
+                               all their fields (name, counter, last_access,
+                               score and alias), in FORMAT: 'json' (default),
+                               'csv' or 'tsv'.
+  import --format json [--dry-run] FILE
+                             restore the directories from a JSON export;
+                               for directories already in the database, the
+                               highest counter and the latest access are kept.
+  import --from FORMAT [--dry-run] FILE
+                             import the directories from the data FILE of
+                               another tool, with FORMAT: 'z' (~/.z), 'fasd'
+                               (~/.fasd), 'zoxide' (the output of
+                               'zoxide query --list --score'), 'autojump'
+                               (autojump.txt), 'bash-history' or
+                               'zsh-history' (absolute 'cd' targets only).
+                               Only existing directories are imported; the
+                               counters of directories already in the
+                               database are added up. With '--dry-run', only
+                               show what would be imported.
+  init SHELL [--cmd NAME] [--hook HOOK]
+                             print the shell integration (the 'z' function,
+                               and its completion, except for 'posix') for
+                               SHELL ('bash', 'zsh', 'fish' or 'posix');
+                               e.g., add 'eval \"$(cz init bash)\"' to
+                               '.bashrc', or 'cz init fish | source' to
+                               'config.fish'. '--cmd' sets the name of the
+                               function (default: 'z'; letters, digits and
+                               '_'). '--hook' sets when
+                               the current directory is recorded: 'none',
+                               'prompt' (on every prompt) or 'pwd' (on every
+                               directory change; default, except for 'posix',
+                               which only supports 'prompt').
+  jump [ARGUMENT]...         run 'z' with the arguments, which are never
+                               taken as commands, so 'z add' goes to a
+                               directory that matches 'add' (used by the shell
+                               functions).
+  merge [--policy POLICY] [--aliases POLICY] [--rewrite FROM=TO]...
+        [--dry-run] DATABASE
+                             merge another directories database (e.g., from
+                               another machine) into this one. '--policy'
+                               sets how the counters of the directories in
+                               both databases are combined: 'max' (default;
+                               keep the highest one, so merging the same
+                               database twice changes nothing) or 'sum'. The
+                               latest access is always kept. '--aliases' sets
+                               how alias clashes are resolved: 'report'
+                               (default; keep the local aliases and list the
+                               clashes), 'local' or 'other' (the aliases of
+                               DATABASE win). '--rewrite' maps the paths
+                               under FROM onto TO (e.g.,
+                               '--rewrite /home/alice=/Users/alice'); it can
+                               be repeated. With '--dry-run', only show what
+                               would be merged.
+  query [--list] [--score] [--json] [SUBSTRING]...
+                             print the directory that 'z SUBSTRING...' would
+                               go to (the one with the highest score, instead
+                               of asking), or, with '--list', all the
+                               matching directories, one per line and without
+                               colors, for scripts and editor plugins.
+                               '--score' adds the scores, and '--json' prints
+                               each directory as a JSON object. The exit
+                               status is 1 if no directory matches.
+
 Exit status:
  0  if OK,
  1  if minor problems (e.g., cannot access subdirectory)
@@ -85,7 +214,7 @@ Configuration options:
               dir).
           - 'none': show selection list.
           - 'basename': go to the directory with the highest score (most 'frecent'
-              dir) whose _basename_ matches the substring(s).
+              dir) whose _basename_ contains the substring(s), in order.
   show_files: string. Whether to show non-dir files, and where, in
       interactive selection.
       * Allowed values:
@@ -95,21 +224,109 @@ Configuration options:
   nav_start_number: int. Start number for interactive navigation, that is,
       the number that the parent directory will have.
       * Recommended values: 1 or 0.
+  max_age: int. Maximum age, in days since the last access, of the
+      directories kept by 'db prune'; 0 means no limit.
+  max_entries: int. Maximum number of directories (the ones with the highest
+      score) kept by 'db prune'; 0 means no limit.
+  max_total_count: int. Maximum sum of the visit counters of all the
+      directories. If it is exceeded, 'db prune' ages the database like z and
+      zoxide do: all the counters are scaled down so that they add up to 90%
+      of the maximum, and the directories whose counter drops below 1 are
+      removed. 0 means no limit.
+  keep_aliased: bool. Never remove aliased directories when pruning.
+  score_model: string. How the score ('frecency') of the directories, used
+      to sort them everywhere, is computed.
+      * Allowed values:
+          - 'frecency': visit count with a continuous decay over the time since
+              the last access: 10000 * count * 3.75 / (score_decay * age + 1.25).
+          - 'buckets': visit count multiplied by a factor that depends on
+              whether the last access was within the last hour, day, week, or
+              before (see 'score_buckets'), as classic z and zoxide do.
+          - 'count': visit count only.
+          - 'recency': time of the last access only.
+  score_decay: float. Decay rate, per second, of the 'frecency' model.
+  score_buckets: string. Comma-separated factors of the 'buckets' model for
+      directories accessed within the last hour, day, week, and before.
+  matcher: string. How substrings are matched against the directories.
+      * Allowed values:
+          - 'substring': every substring must appear, as is, in the path.
+          - 'fuzzy': the characters of every substring must appear in the
+              path in the same order, but not necessarily together (e.g.,
+              'prjwb' matches 'projects/web'). Matches at the start of path
+              segments and words, and contiguous ones, are preferred: the
+              score of each directory is weighted by how well it matches.
+          - 'ordered': the substrings must appear in the path in the same
+              order, and the last one in its final component (e.g., 'src app'
+              matches '~/src/web/app', but neither '~/app/src' nor
+              '~/src/app/old').
+  case_sensitivity: string. Whether the case matters when matching
+      substrings and aliases.
+      * Allowed values:
+          - 'smart': ignore the case, unless the substrings contain uppercase
+              letters.
+          - 'sensitive': always take the case into account.
+          - 'insensitive': always ignore the case.
+  fold_unicode: bool. Ignore the diacritics of Latin letters when matching
+      substrings and aliases (e.g., 'resume' matches 'Résumé'), both in
+      composed and decomposed names.
+  exclude: string. Glob patterns, separated by ':', of the directories that
+      are not recorded (e.g., '/tmp/*:~/mnt/**:node_modules:target'). A
+      pattern without '/' matches any component of the path; otherwise, it
+      matches the whole path. Subdirectories of excluded directories are also
+      excluded, and explicitly aliased directories are always recorded. More
+      patterns, one per line, can be put in
+      '~/.config/contemporary-z/.czignore'.
+  fallback: bool. When no recorded directory matches the substrings, look
+      for them in the filesystem, and jump to (and record) the first found
+      directory: as a path relative to the current directory, then to the
+      'CDPATH' entries, and then among the subdirectories of the top
+      directories (the one whose name matches best, with the configured
+      'matcher').
+  fallback_dirs: int. Number of top directories whose subdirectories are
+      searched by the fallback.
+  proximity_boost: float. Boost the score of the directories near the
+      current one, when matching substrings and listing directories: it is
+      multiplied by 1 + proximity_boost * proximity, where proximity is 1
+      for the directories inside the current one, and it halves with each
+      level that has to be climbed from the current directory to reach them
+      (e.g., 0.5 for its siblings). 0 disables the boost.
+  proximity_git_root: bool. With proximity_boost, the proximity of the
+      directories in the same git repository as the current one is 1.
+  current_dir: string. What to do with the current directory when jumping
+      to and selecting directories ('-l' and '--list-all' list it as usual).
+      * Allowed values:
+          - 'skip': never show it nor jump to it.
+          - 'demote': put it after all the other directories, so that the
+              next best one is chosen.
+          - 'keep': treat it like any other directory.
+  picker: string. How directories are selected interactively.
+      * Allowed values:
+          - 'numbered' (default): numbered list, and a prompt to introduce
+              the number. Long lists are split into pages of max_results
+              directories: 'n' shows the next page, 'p' the previous one,
+              and 'f' and 'l' the first and the last ones. The numbers go on
+              across the pages, so any directory can be selected from any
+              page.
+          - 'builtin': full-screen list, filtered as you type (the characters
+              that match are highlighted), with the full path of the current
+              entry at the bottom. Up/Down or Ctrl-P/Ctrl-N move, PageUp/
+              PageDown move a page, Tab marks several entries (when
+              removing), Enter selects, and Esc or Ctrl-C cancels.
+              Ctrl-U clears the query, and Ctrl-W deletes its last word. In
+              dumb terminals, the numbered list is used instead.
+          - 'fzf', 'sk', 'peco': external fuzzy finder, which gets the
+              directories with their score and alias; several directories
+              can be selected when removing. If the program is not
+              installed, the numbered list is used instead. The interactive
+              navigation ('-i') uses the 'builtin' picker.
 
 
 Default config:
 -------------------------------------------------------------------
 # ~/.config/contemporary-z/cz.conf
 
-theme = dark
-max_results = 9
-abs_paths = true
-compact_paths = true
-database_path = $HOME/.local/share/contemporary-z/directories.dir
-substring = score
-show_files = none
-nav_start_number = 1
+", default_config!(), "\
 -------------------------------------------------------------------
 
 Source code: <https://github.com/j-morano/contemporary-z>
-";
+");
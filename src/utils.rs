@@ -1,6 +1,10 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::io;
 use std::io::Write;
+use std::env;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::os::unix::process;
 
 
 
@@ -30,52 +34,81 @@ pub(crate) fn canonicalize_dir_str(dir_str_name: &str) -> String {
 }
 
 
-pub(crate) fn write_dir(path: String) {
-    // Open file in read mode
-    let mut z_file = match fs::OpenOptions::new()
-        .read(true)
-        .write(false)
-        .open("/tmp/cz_path") {
-            Err(_) => {
-                // Open file in write mode
-                fs::OpenOptions::new()
-                    .read(true)
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open("/tmp/cz_path")
-                    .expect("Could not open file")
-            },
-            Ok(file) => {
-                // Set writeable
-                let mut permissions = file.metadata().expect(
-                    "Could not get metadata"
-                    ).permissions();
-                permissions.set_readonly(false);
-                file.set_permissions(permissions.clone()).expect(
-                    "Could not set permissions."
-                    );
-                // Open file in write mode
-                fs::OpenOptions::new()
-                    .read(true)
-                    .write(true)
-                    .truncate(true)
-                    .open("/tmp/cz_path")
-                    .expect("Could not open file")
-            }
+/// Root of the git repository that contains the dir, if any.
+pub(crate) fn git_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .map(|root| root.to_path_buf())
+}
+
+
+/// Return the file through which the selected dir is handed back to the
+/// shell function.
+fn output_path() -> PathBuf {
+    // The shell function can pass its own (private) file
+    if let Ok(path) = env::var("CZ_OUTPUT") {
+        if !path.is_empty() {
+            return PathBuf::from(path);
+        }
+    }
+
+    // Otherwise, use a per-session file, named after the parent process (the
+    //   shell), inside a private per-user dir
+    let uid = fs::metadata("/proc/self")
+        .expect("Could not get process metadata")
+        .uid();
+    let output_dir = match env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("contemporary-z"),
+        _ => env::temp_dir().join(format!("contemporary-z-{}", uid)),
+    };
+    if let Err(e) = fs::DirBuilder::new().mode(0o700).create(&output_dir) {
+        if e.kind() != io::ErrorKind::AlreadyExists {
+            panic!("Error creating dir: {}", e);
+        }
+    }
+    // Do not trust a dir that could have been created by another user
+    let dir_metadata = fs::symlink_metadata(&output_dir)
+        .expect("Could not get metadata");
+    if !dir_metadata.is_dir()
+        || dir_metadata.uid() != uid
+        || dir_metadata.mode() & 0o077 != 0
+    {
+        panic!("Unsafe output dir: {}", output_dir.display());
+    }
+
+    remove_stale_outputs(&output_dir);
+    output_dir.join(format!("path.{}", process::parent_id()))
+}
+
+
+/// Remove the per-session files of the shells that are no longer running,
+/// which are left behind since nothing else deletes them.
+fn remove_stale_outputs(output_dir: &Path) {
+    let entries = match fs::read_dir(output_dir) {
+        Ok(entries) => entries,
+        Err(_) => { return; }
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let pid = match file_name.strip_prefix("path.") {
+            Some(pid) if pid.parse::<u32>().is_ok() => pid.to_string(),
+            _ => { continue; }
         };
+        if !Path::new("/proc").join(&pid).exists() {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+
+pub(crate) fn write_dir(path: String) {
+    let mut z_file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(output_path())
+        .expect("Could not open file");
     // Write action
-    z_file.write_all(
-        format!("{}", path).as_bytes()
-        ).expect("Could not write to file");
-    // Set read-only again
-    let mut permissions = z_file.metadata().expect(
-        "Could not get metadata"
-        ).permissions();
-    permissions.set_readonly(false);
-    z_file.set_permissions(permissions.clone()).expect(
-        "Could not set permissions."
-        );
-    permissions.set_readonly(true);
-    z_file.set_permissions(permissions).expect("Could not set permissions.");
+    z_file.write_all(path.as_bytes()).expect("Could not write to file");
 }
@@ -0,0 +1,12 @@
+function z
+    set -l cz_tmpdir /tmp
+    set -q TMPDIR[1]; and set cz_tmpdir $TMPDIR
+    set -l cz_out (mktemp "$cz_tmpdir/cz.XXXXXX")
+    or return 1
+    env CZ_OUTPUT=$cz_out cz jump $argv
+    set -l zout (cat $cz_out)
+    rm -f $cz_out
+    if test -n "$zout"
+        cd $zout
+    end
+end
@@ -1,7 +1,10 @@
 
 z() {
-    cz "$@"
-    zout=$(cat "/tmp/cz_path")
+    local cz_out zout
+    cz_out=$(mktemp "${TMPDIR:-/tmp}/cz.XXXXXX") || return 1
+    CZ_OUTPUT="$cz_out" cz jump "$@"
+    zout=$(cat "$cz_out")
+    rm -f "$cz_out"
     if [[ -n "$zout" ]]; then
         cd "$zout"
     fi